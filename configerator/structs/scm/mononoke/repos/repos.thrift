@@ -219,6 +219,10 @@ struct RawRepoConfig {
   //  Mononoke will not parse and validate anything about LFS pointer contents.
   // (flag not set defaults to false)
   61: optional bool git_lfs_interpret_pointers;
+  // Maximum number of simultaneously-served requests for this repo.
+  // Requests beyond the limit wait for a slot to free up. Zero or unset
+  // means unbounded.
+  62: optional i64 max_concurrent_requests;
 }
 
 // Configuration for connecting to Zelos
@@ -786,6 +790,29 @@ struct RawCommonConfig {
 
   // Scuba table to dump edenapi requests to (for replay)
   12: optional string edenapi_dumper_scuba_table;
+
+  // How long to wait, in seconds, for in-flight connections to finish after
+  // the server stops accepting new ones during shutdown. Connections still
+  // open after the timeout are force-closed. Unset means shutdown does not
+  // wait for in-flight connections.
+  13: optional i64 connection_drain_timeout_secs;
+
+  // Maximum number of simultaneously-served connections. New connections
+  // beyond the limit are accepted but wait for a slot to free up. Zero or
+  // unset means unlimited.
+  14: optional i64 max_concurrent_connections;
+
+  // Close a connection whose client has sent no bytes for this many
+  // seconds. Zero or unset disables the idle timeout.
+  15: optional i64 connection_idle_timeout_secs;
+
+  // Accept backlog applied when binding the listening socket. Zero or
+  // unset uses the default (1024).
+  16: optional i32 listen_backlog;
+
+  // Set SO_REUSEADDR on the listening socket so rapid restarts do not
+  // fail with "address already in use". Unset means false.
+  17: optional bool reuse_address;
 }
 
 @rust.Exhaustive
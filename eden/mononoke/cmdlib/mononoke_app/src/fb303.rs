@@ -53,6 +53,10 @@ impl ReadyFlagService {
     pub fn set_ready(&self) {
         self.ready.store(true, Ordering::Relaxed);
     }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
 }
 
 impl Fb303Service for ReadyFlagService {
@@ -8,6 +8,8 @@
 use std::fmt::Display;
 
 use anyhow::Result;
+use futures::stream::StreamExt;
+use futures::Future;
 use futures::Stream;
 use gix_hash::ObjectId;
 use tokio::io::AsyncWrite;
@@ -98,9 +100,42 @@ impl<T: AsyncWrite + Unpin> BundleWriter<T> {
         self.pack_writer.write(objects_stream).await
     }
 
+    /// Write a stream of futures resolving to input items to the bundle,
+    /// driving up to `concurrency` futures at a time (e.g. concurrent
+    /// blobstore fetches) while preserving the stream's order. The written
+    /// bytes and checksum do not depend on the concurrency level.
+    pub async fn write_concurrent<Fut>(
+        &mut self,
+        objects_stream: impl Stream<Item = Fut>,
+        concurrency: usize,
+    ) -> Result<()>
+    where
+        Fut: Future<Output = Result<PackfileItem>>,
+    {
+        self.pack_writer
+            .write(objects_stream.buffered(concurrency.max(1)))
+            .await
+    }
+
     /// Finish the bundle and flush it to the underlying writer
-    /// returning the checksum of the written packfile
+    /// returning the checksum of the written packfile.
+    ///
+    /// Fails if any ref points to an object that was neither streamed
+    /// through `write` nor declared as a prerequisite, since such a bundle
+    /// would reference objects the receiver cannot reach.
     pub async fn finish(&mut self) -> Result<ObjectId> {
+        let dangling_refs = self
+            .refs
+            .iter()
+            .filter(|(_, id)| !self.pack_writer.contains_object(id) && !self.prereqs.contains(id))
+            .map(|(ref_name, id)| format!("{} -> {}", ref_name, id))
+            .collect::<Vec<_>>();
+        if !dangling_refs.is_empty() {
+            anyhow::bail!(
+                "refs point to objects that are not included in the bundle and not declared as prerequisites: {}",
+                dangling_refs.join(", ")
+            );
+        }
         self.pack_writer.finish().await
     }
 
@@ -6,19 +6,28 @@
  */
 
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::hash::BuildHasherDefault;
+use std::io::Read;
 use std::io::Write;
 
 use anyhow::Context;
 use anyhow::Result;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use futures::Stream;
 use futures::StreamExt;
+use gix_hash::oid;
 use gix_hash::ObjectId;
+use gix_object::Kind;
+use gix_pack::data::entry::Header as EntryHeader;
 use gix_pack::data::header;
 use gix_pack::data::output::Entry;
 use gix_pack::data::Version;
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHasher;
+use sha1::Digest;
+use sha1::Sha1;
 use thiserror::Error;
 use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
@@ -40,8 +49,65 @@ pub enum DeltaForm {
     OnlyOffset,
 }
 
+/// Buffer size used by [`PackfileWriter::write_object_streaming`]
+const STREAMING_CHUNK_SIZE: usize = 8192;
+
+/// Per-kind counts of the objects written to a packfile, useful for
+/// telemetry about the pack's composition without re-reading the pack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ObjectTypeCounts {
+    /// The number of base commit objects in the packfile
+    pub commits: u32,
+    /// The number of base tree objects in the packfile
+    pub trees: u32,
+    /// The number of base blob objects in the packfile
+    pub blobs: u32,
+    /// The number of base tag objects in the packfile
+    pub tags: u32,
+    /// The number of deltified objects in the packfile. Their underlying
+    /// kind is only known once the delta chain is resolved, so they are
+    /// counted separately.
+    pub deltas: u32,
+}
+
+/// Bounds how many written object ids the writer remembers for duplicate
+/// skipping. Tracks insertion order; when the bound is exceeded, the oldest
+/// ids are evicted and the writer forgets them entirely (they are removed
+/// from `object_id_with_index`), so memory really is capped. A duplicate of
+/// an evicted object is written again, which is wasteful but valid.
+struct SeenObjectFilter {
+    capacity: Option<usize>,
+    order: VecDeque<ObjectId>,
+}
+
+impl SeenObjectFilter {
+    fn new(capacity: Option<usize>) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Record `id` as written, returning the ids evicted to stay within
+    /// the capacity. The caller must not insert an id it still remembers.
+    fn insert(&mut self, id: ObjectId) -> Vec<ObjectId> {
+        self.order.push_back(id);
+        let mut evicted = Vec::new();
+        if let Some(capacity) = self.capacity {
+            while self.order.len() > capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    evicted.push(oldest);
+                }
+            }
+        }
+        evicted
+    }
+}
+
 /// Struct responsible for encoding and writing incoming stream
-/// of git object bytes as a packfile to `raw_writer`.
+/// of git object bytes as a packfile to `raw_writer`. The writer is generic
+/// over [`AsyncWrite`], so the pack can be streamed directly to e.g. a tokio
+/// socket without an intermediate buffer.
 /// NOTE: The caller must ensure that the stream of objects passed to this
 /// writer are sorted topologically
 pub struct PackfileWriter<T>
@@ -70,6 +136,10 @@ where
     delta_form: DeltaForm,
     /// Mapping from Object Id to index in `object_offset_with_validity`
     object_id_with_index: FxHashMap<ObjectId, usize>,
+    /// Optional filter for skipping objects whose id was already written
+    seen_filter: Option<SeenObjectFilter>,
+    /// Per-kind counts of the objects written so far
+    object_type_counts: ObjectTypeCounts,
 }
 
 impl<T: AsyncWrite + Unpin> PackfileWriter<T> {
@@ -90,9 +160,82 @@ impl<T: AsyncWrite + Unpin> PackfileWriter<T> {
                 BuildHasherDefault::<FxHasher>::default(),
             ),
             delta_form,
+            seen_filter: None,
+            object_type_counts: ObjectTypeCounts::default(),
         }
     }
 
+    /// Resume writing a packfile whose first bytes (header included) were
+    /// already persisted by an earlier writer that died before `finish`.
+    ///
+    /// Since the trailing checksum is the SHA1 of the entire pack content,
+    /// the running hash must be reconstructed by re-hashing the bytes that
+    /// were already written, so callers must provide them in
+    /// `previously_written`. `num_entries_so_far` is the number of objects
+    /// contained in `previously_written` and `expected_objects` is the total
+    /// object count recorded in the pack header.
+    ///
+    /// NOTE: Offsets of previously written objects are not reconstructed, so
+    /// objects appended after a resume cannot refer to pre-resume objects as
+    /// delta bases.
+    pub fn resume(
+        raw_writer: T,
+        previously_written: &[u8],
+        num_entries_so_far: u32,
+        expected_objects: u32,
+        concurrency: usize,
+        delta_form: DeltaForm,
+    ) -> Self {
+        let mut hash_writer = AsyncHashWriter::new(raw_writer);
+        hash_writer.hasher.update(previously_written);
+        let remaining = expected_objects.saturating_sub(num_entries_so_far);
+        Self {
+            hash_writer,
+            num_entries: num_entries_so_far,
+            size: previously_written.len() as u64,
+            hash: None,
+            concurrency,
+            // The header is part of the previously written bytes, unless
+            // nothing was written at all before the interruption.
+            header_info: if previously_written.is_empty() {
+                Some((Version::V2, expected_objects))
+            } else {
+                None
+            },
+            object_offset_with_validity: Vec::with_capacity(remaining as usize),
+            object_id_with_index: HashMap::with_capacity_and_hasher(
+                remaining as usize,
+                BuildHasherDefault::<FxHasher>::default(),
+            ),
+            delta_form,
+            seen_filter: None,
+            // Pre-resume entry headers are not re-parsed, so the counts only
+            // cover objects appended after the resume.
+            object_type_counts: ObjectTypeCounts::default(),
+        }
+    }
+
+    /// Bound the number of written object ids remembered for duplicate
+    /// skipping, useful when streaming objects from many overlapping
+    /// sources where remembering every id would not fit in memory.
+    ///
+    /// By default (and with `capacity` of `None`) every written id is
+    /// remembered and every duplicate is skipped. With a bound, the oldest
+    /// ids are forgotten once the bound is exceeded: a duplicate of a
+    /// forgotten object is written again (wasteful but valid), and with
+    /// [`DeltaForm::OnlyOffset`] a ref delta whose base was forgotten can
+    /// no longer be converted.
+    pub fn with_seen_filter(mut self, capacity: Option<usize>) -> Self {
+        self.seen_filter = Some(SeenObjectFilter::new(capacity));
+        self
+    }
+
+    /// Returns true if an object with the given id was written to this
+    /// packfile.
+    pub fn contains_object(&self, id: &ObjectId) -> bool {
+        self.object_id_with_index.contains_key(id)
+    }
+
     /// Write the packfile header information if it hasn't been written yet.
     async fn write_header(&mut self) -> Result<()> {
         if let Some((version, count)) = self.header_info.take() {
@@ -130,6 +273,13 @@ impl<T: AsyncWrite + Unpin> PackfileWriter<T> {
                     continue;
                 }
                 self.record_entry(&entry);
+                // If remembered ids are bounded, forget the evicted ones so
+                // the id map does not grow past the bound
+                if let Some(seen_filter) = self.seen_filter.as_mut() {
+                    for evicted in seen_filter.insert(entry.id.clone()) {
+                        self.object_id_with_index.remove(&evicted);
+                    }
+                }
                 // If the current entry is a ref delta and we can only have offset deltas, then convert the ref delta
                 // to an offset delta. Otherwise, return the entry as-is
                 entry = self.convert_ref_delta_to_offset_delta(entry)?;
@@ -157,11 +307,125 @@ impl<T: AsyncWrite + Unpin> PackfileWriter<T> {
                     tokio::io::copy(&mut &*entry.compressed_data, &mut self.hash_writer).await?;
                 // Increment the number of entries written in the packfile
                 self.num_entries += 1;
+                self.record_entry_kind(&header);
             }
         }
         Ok(())
     }
 
+    /// Write a single base object to the packfile, computing its id and
+    /// zlib-encoding while streaming from `reader` in fixed-size buffers, so
+    /// peak memory is bounded by the buffer size rather than the object size.
+    ///
+    /// `size` is the decompressed size of the object content and must match
+    /// the number of bytes yielded by `reader`. The resulting pack entry is
+    /// byte-identical to the buffered [`PackfileItem`] path for the same
+    /// input. Since the object id is only known once the entry has been
+    /// written, already-written duplicates cannot be skipped by this method.
+    pub async fn write_object_streaming<R: Read>(
+        &mut self,
+        kind: Kind,
+        size: u64,
+        mut reader: R,
+    ) -> Result<ObjectId> {
+        // Write the packfile header if applicable
+        self.write_header().await?;
+        // The current object will be written at offset `size`
+        let entry_offset = self.size;
+        // The entry header only depends on the kind and decompressed size, so
+        // it can be written before the object bytes are consumed
+        let entry_header = match kind {
+            Kind::Commit => EntryHeader::Commit,
+            Kind::Tree => EntryHeader::Tree,
+            Kind::Blob => EntryHeader::Blob,
+            Kind::Tag => EntryHeader::Tag,
+        };
+        let mut header_buffer = Vec::new();
+        let header_written_size = entry_header.write_to(size, &mut header_buffer.by_ref())?;
+        self.hash_writer
+            .write_all(&header_buffer[..header_written_size])
+            .await?;
+        self.size += header_written_size as u64;
+        // The object id is the SHA1 of the loose format, i.e. "<kind> <size>\0"
+        // followed by the object content
+        let mut hasher = Sha1::new();
+        hasher.update(kind.as_bytes());
+        hasher.update(b" ");
+        hasher.update(size.to_string().as_bytes());
+        hasher.update(b"\0");
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        let mut chunk = vec![0u8; STREAMING_CHUNK_SIZE];
+        let mut total_read = 0u64;
+        loop {
+            let read = reader
+                .read(&mut chunk)
+                .context("Failure in reading streamed Git object data")?;
+            if read == 0 {
+                break;
+            }
+            total_read += read as u64;
+            hasher.update(&chunk[..read]);
+            encoder
+                .write_all(&chunk[..read])
+                .context("Failure in writing streamed Git object data to ZLib buffer")?;
+            // Drain whatever the encoder produced for this chunk so the
+            // compressed output is never buffered in full
+            let compressed = encoder.get_mut();
+            if !compressed.is_empty() {
+                self.hash_writer.write_all(compressed).await?;
+                self.size += compressed.len() as u64;
+                compressed.clear();
+            }
+        }
+        if total_read != size {
+            anyhow::bail!(
+                "Streamed object size mismatch: expected {} bytes but read {}",
+                size,
+                total_read
+            );
+        }
+        let compressed = encoder
+            .finish()
+            .context("Failure in ZLib encoding streamed Git object data")?;
+        self.hash_writer.write_all(&compressed).await?;
+        self.size += compressed.len() as u64;
+        let id: ObjectId = oid::try_from_bytes(hasher.finalize().as_ref())
+            .context("Failed to convert streamed object hash to Git Object ID")?
+            .into();
+        // Record the entry the same way as the buffered path. A duplicate of
+        // an already-written object keeps the original's index mapping and is
+        // not re-inserted into the seen filter.
+        let is_new = !self.object_id_with_index.contains_key(&id);
+        self.object_offset_with_validity.push((entry_offset, true));
+        self.object_id_with_index
+            .entry(id.clone())
+            .or_insert(self.object_offset_with_validity.len() - 1);
+        if is_new {
+            if let Some(seen_filter) = self.seen_filter.as_mut() {
+                for evicted in seen_filter.insert(id.clone()) {
+                    self.object_id_with_index.remove(&evicted);
+                }
+            }
+        }
+        self.num_entries += 1;
+        self.record_entry_kind(&entry_header);
+        Ok(id)
+    }
+
+    /// Per-kind counts of the objects written so far. The counts are complete
+    /// once `finish` has been called.
+    pub fn object_type_counts(&self) -> ObjectTypeCounts {
+        self.object_type_counts
+    }
+
+    /// The hash of all bytes written so far (header + encoded objects), i.e.
+    /// the checksum that `finish` would produce if called right now. Does not
+    /// consume or finalize the writer; useful for integrity checkpoints
+    /// during a long pack write and for resume implementations.
+    pub fn current_hash(&self) -> ObjectId {
+        ObjectId::from(self.hash_writer.hasher.clone().digest())
+    }
+
     /// Finish the packfile by writing the trailer at the end and returning the checksum
     /// hash of the generated file.
     pub async fn finish(&mut self) -> Result<ObjectId> {
@@ -182,6 +446,14 @@ impl<T: AsyncWrite + Unpin> PackfileWriter<T> {
         self.hash_writer.inner
     }
 
+    /// Abandon the pack midway and reclaim the underlying raw writer, e.g.
+    /// to clean up after a failed `write`. No trailer or checksum is
+    /// written: the writer holds a partial pack. Unlike `into_write`, this
+    /// does not imply a completed pack and can be called before `finish`.
+    pub fn abort(self) -> T {
+        self.hash_writer.inner
+    }
+
     fn convert_ref_delta_to_offset_delta(&self, entry: Entry) -> Result<Entry> {
         use gix_pack::data::output::entry::Kind::*;
         match self.delta_form {
@@ -204,6 +476,18 @@ impl<T: AsyncWrite + Unpin> PackfileWriter<T> {
         }
     }
 
+    fn record_entry_kind(&mut self, header: &EntryHeader) {
+        match header {
+            EntryHeader::Commit => self.object_type_counts.commits += 1,
+            EntryHeader::Tree => self.object_type_counts.trees += 1,
+            EntryHeader::Blob => self.object_type_counts.blobs += 1,
+            EntryHeader::Tag => self.object_type_counts.tags += 1,
+            EntryHeader::RefDelta { .. } | EntryHeader::OfsDelta { .. } => {
+                self.object_type_counts.deltas += 1
+            }
+        }
+    }
+
     fn record_entry(&mut self, entry: &Entry) {
         // Will be false for all our cases since we generate the entry with the object ID in hand.
         // Including here for sake of completeness.
@@ -64,6 +64,36 @@ impl PackfileItem {
             compressed_data,
         ))
     }
+
+    /// The ObjectId of the Git object represented by this item, so callers
+    /// can correlate written objects with their ids (e.g. to build an
+    /// oid -> offset side table) without re-hashing the content.
+    pub fn object_id(&self) -> ObjectId {
+        match self {
+            Self::Base(base) => base.hash.clone(),
+            Self::EncodedBase(entry) => entry.id.clone(),
+            Self::OidDelta(oid_delta) => oid_delta.oid.clone(),
+        }
+    }
+
+    /// The decompressed size of the item's payload. For delta items this is
+    /// the size of the delta instructions, not of the reconstructed object.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Base(base) => base.size(),
+            Self::EncodedBase(entry) => entry.decompressed_size,
+            Self::OidDelta(oid_delta) => oid_delta.decompressed_size,
+        }
+    }
+
+    /// The packfile entry kind of this item.
+    pub fn kind(&self) -> output::entry::Kind {
+        match self {
+            Self::Base(base) => base.kind(),
+            Self::EncodedBase(entry) => entry.kind.clone(),
+            Self::OidDelta(oid_delta) => oid_delta.kind(),
+        }
+    }
 }
 
 impl TryFrom<PackfileItem> for output::Entry {
@@ -14,6 +14,7 @@ use flate2::write::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use futures::stream;
+use futures::stream::StreamExt;
 use git_types::DeltaInstructions;
 use gix_diff::blob::Algorithm;
 use gix_hash::ObjectId;
@@ -22,6 +23,7 @@ use gix_object::ObjectRef;
 use gix_object::Tag;
 use packfile::bundle::BundleWriter;
 use packfile::pack::DeltaForm;
+use packfile::pack::ObjectTypeCounts;
 use packfile::pack::PackfileWriter;
 use packfile::thrift;
 use packfile::types::to_vec_bytes;
@@ -110,6 +112,30 @@ fn validate_packitem_creation() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn validate_packfile_item_accessors() -> anyhow::Result<()> {
+    // Create a blob with known content
+    let blob_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Blob(gix_object::Blob {
+        data: "hello world\n".as_bytes().to_vec(),
+    }))?);
+    let item = PackfileItem::new_base(blob_bytes)?;
+    // The item's id is the git hash of the content, without re-hashing
+    let expected_id = ObjectId::from_hex(b"3b18e512dba79e4c8300dd08aeb37f8e728b8dad")?;
+    assert_eq!(item.object_id(), expected_id);
+    assert_eq!(item.size(), "hello world\n".len());
+    assert_eq!(
+        item.kind(),
+        gix_pack::data::output::entry::Kind::Base(gix_object::Kind::Blob)
+    );
+    // An empty blob hashes to the well-known empty-blob id
+    let empty_blob_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Blob(
+        gix_object::Blob { data: Vec::new() },
+    ))?);
+    let item = PackfileItem::new_base(empty_blob_bytes)?;
+    assert_eq!(item.object_id(), ObjectId::empty_blob(gix_hash::Kind::Sha1));
+    Ok(())
+}
+
 #[test]
 fn validate_packfile_item_encoding() -> anyhow::Result<()> {
     // Create a Git object
@@ -271,6 +297,313 @@ async fn validate_staggered_packfile_generation() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn validate_current_hash_during_packfile_generation() -> anyhow::Result<()> {
+    let concurrency = 100;
+    let mut packfile_writer =
+        PackfileWriter::new(Vec::new(), 3, concurrency, DeltaForm::RefAndOffset);
+    // Write the first two objects to the packfile
+    let objects_stream = get_objects_stream(false).await?;
+    packfile_writer
+        .write(objects_stream.take(2))
+        .await
+        .expect("Expected successful write of objects to packfile");
+    // Record the running hash as of the last fully-written object
+    let intermediate_hash = packfile_writer.current_hash();
+    // Write the third object to the packfile
+    let objects_stream = get_objects_stream(false).await?;
+    packfile_writer
+        .write(objects_stream.skip(2))
+        .await
+        .expect("Expected successful write of object to packfile");
+    // The running hash covers the new bytes, so it moved past the checkpoint
+    let final_running_hash = packfile_writer.current_hash();
+    assert_ne!(intermediate_hash, final_running_hash);
+    // Validate we are able to finish writing to the packfile and generate the final checksum
+    let checksum = packfile_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    // The final checksum is the running hash at the time `finish` was called,
+    // which is what a resume implementation relies on
+    assert_eq!(checksum, final_running_hash);
+    assert_ne!(checksum, intermediate_hash);
+    Ok(())
+}
+
+#[fbinit::test]
+async fn validate_aborted_packfile_reclaims_writer() -> anyhow::Result<()> {
+    let concurrency = 100;
+    let mut packfile_writer =
+        PackfileWriter::new(Vec::new(), 3, concurrency, DeltaForm::RefAndOffset);
+    // Write a single object to the packfile
+    let objects_stream = get_objects_stream(false).await?;
+    packfile_writer
+        .write(objects_stream.take(1))
+        .await
+        .expect("Expected successful write of object to packfile");
+    let size = packfile_writer.size;
+    // Abort instead of finishing: the raw writer comes back without a
+    // trailer checksum being appended
+    let reclaimed = packfile_writer.abort();
+    // The partial pack starts with the pack header magic
+    assert_eq!(&reclaimed[..4], b"PACK");
+    // Nothing was appended beyond the bytes written so far
+    assert_eq!(reclaimed.len(), size as usize);
+    Ok(())
+}
+
+#[fbinit::test]
+async fn validate_resumed_packfile_generation() -> anyhow::Result<()> {
+    let concurrency = 100;
+    let mut packfile_writer =
+        PackfileWriter::new(Vec::new(), 3, concurrency, DeltaForm::RefAndOffset);
+    // Write the first two objects with the initial writer
+    let tag_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Tag(Tag {
+        target: ObjectId::empty_tree(gix_hash::Kind::Sha1),
+        target_kind: gix_object::Kind::Tree,
+        name: "TreeTag".into(),
+        tagger: None,
+        message: "Tag pointing to a tree".into(),
+        pgp_signature: None,
+    }))?);
+    let blob_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Blob(gix_object::Blob {
+        data: "Some file content".as_bytes().to_vec(),
+    }))?);
+    packfile_writer
+        .write(stream::iter(vec![
+            PackfileItem::new_base(tag_bytes),
+            PackfileItem::new_base(blob_bytes),
+        ]))
+        .await
+        .expect("Expected successful write of objects to packfile");
+    let num_entries_so_far = packfile_writer.num_entries;
+    // Simulate the process dying before `finish` by dropping the writer and
+    // keeping only the bytes written so far
+    let previously_written = packfile_writer.into_write();
+
+    // Resume with a fresh writer and append the third object
+    let mut packfile_writer = PackfileWriter::resume(
+        Vec::new(),
+        &previously_written,
+        num_entries_so_far,
+        3,
+        concurrency,
+        DeltaForm::RefAndOffset,
+    );
+    let tree_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Tree(gix_object::Tree {
+        entries: vec![gix_object::tree::Entry {
+            mode: gix_object::tree::EntryMode::Blob,
+            filename: "JustAFile.txt".into(),
+            oid: ObjectId::empty_blob(gix_hash::Kind::Sha1),
+        }],
+    }))?);
+    packfile_writer
+        .write(stream::iter(vec![PackfileItem::new_base(tree_bytes)]))
+        .await
+        .expect("Expected successful write of object to resumed packfile");
+    // Validate we are able to finish writing to the packfile and generate the final checksum
+    let checksum = packfile_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    let (num_entries, size) = (packfile_writer.num_entries, packfile_writer.size);
+    assert_eq!(num_entries, 3);
+    // Stitch the pre-resume and post-resume bytes together and verify the
+    // result is a valid packfile
+    let mut written_content = previously_written;
+    written_content.extend(packfile_writer.into_write());
+    let mut created_file = NamedTempFile::new()?;
+    created_file.write_all(written_content.as_ref())?;
+    let opened_packfile = gix_pack::data::File::at(created_file.path(), gix_hash::Kind::Sha1);
+    let opened_packfile = opened_packfile.expect("Expected successful opening of packfile");
+    assert_eq!(opened_packfile.num_objects(), num_entries);
+    assert_eq!(opened_packfile.data_len(), size as usize);
+    let checksum_from_file = opened_packfile
+        .verify_checksum(gix_features::progress::Discard, &AtomicBool::new(false))
+        .expect("Expected successful checksum computation");
+    assert_eq!(checksum, checksum_from_file);
+    Ok(())
+}
+
+#[fbinit::test]
+async fn validate_deduped_packfile_generation() -> anyhow::Result<()> {
+    let concurrency = 100;
+    let mut packfile_writer =
+        PackfileWriter::new(Vec::new(), 2, concurrency, DeltaForm::RefAndOffset)
+            .with_seen_filter(None);
+    // Create a stream containing the same blob twice along with a tree
+    let blob_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Blob(gix_object::Blob {
+        data: "Some file content".as_bytes().to_vec(),
+    }))?);
+    let tree_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Tree(gix_object::Tree {
+        entries: vec![gix_object::tree::Entry {
+            mode: gix_object::tree::EntryMode::Blob,
+            filename: "JustAFile.txt".into(),
+            oid: ObjectId::empty_blob(gix_hash::Kind::Sha1),
+        }],
+    }))?);
+    let objects_stream = stream::iter(vec![
+        PackfileItem::new_base(blob_bytes.clone()),
+        PackfileItem::new_base(blob_bytes.clone()),
+        PackfileItem::new_base(tree_bytes.clone()),
+    ]);
+    // Validate we are able to write the objects to the packfile without errors
+    packfile_writer
+        .write(objects_stream)
+        .await
+        .expect("Expected successful write of objects to packfile");
+    // Validate we are able to finish writing to the packfile and generate the final checksum
+    let checksum = packfile_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    // The duplicate blob should have been skipped by the seen filter
+    let (num_entries, size) = (packfile_writer.num_entries, packfile_writer.size);
+    assert_eq!(num_entries, 2);
+    // The unbounded filter still remembers the blob
+    let blob_id = BaseObject::new(blob_bytes.clone())?.hash().to_owned();
+    assert!(packfile_writer.contains_object(&blob_id));
+    // Retrieve the raw_writer (in this case Vec) back from the PackfileWriter
+    let written_content = packfile_writer.into_write();
+    // Write the packfile to disk
+    let mut created_file = NamedTempFile::new()?;
+    created_file.write_all(written_content.as_ref())?;
+    // Open the written packfile
+    let opened_packfile = gix_pack::data::File::at(created_file.path(), gix_hash::Kind::Sha1);
+    let opened_packfile = opened_packfile.expect("Expected successful opening of packfile");
+    // Validate the packfile contains exactly one copy of the duplicated blob
+    assert_eq!(opened_packfile.num_objects(), num_entries);
+    assert_eq!(opened_packfile.data_len(), size as usize);
+    let checksum_from_file = opened_packfile
+        .verify_checksum(gix_features::progress::Discard, &AtomicBool::new(false))
+        .expect("Expected successful checksum computation");
+    assert_eq!(checksum, checksum_from_file);
+
+    // With a bound of one remembered id, writing the tree evicts the blob,
+    // so a later duplicate of the blob is no longer recognized and gets
+    // written again
+    let mut packfile_writer =
+        PackfileWriter::new(Vec::new(), 3, concurrency, DeltaForm::RefAndOffset)
+            .with_seen_filter(Some(1));
+    let objects_stream = stream::iter(vec![
+        PackfileItem::new_base(blob_bytes.clone()),
+        PackfileItem::new_base(tree_bytes.clone()),
+        PackfileItem::new_base(blob_bytes),
+    ]);
+    packfile_writer
+        .write(objects_stream)
+        .await
+        .expect("Expected successful write of objects to packfile");
+    let checksum = packfile_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    let (num_entries, size) = (packfile_writer.num_entries, packfile_writer.size);
+    assert_eq!(num_entries, 3);
+    // Re-writing the blob re-remembered it and evicted the tree, so only
+    // one id is remembered at any point
+    let tree_id = BaseObject::new(tree_bytes)?.hash().to_owned();
+    assert!(packfile_writer.contains_object(&blob_id));
+    assert!(!packfile_writer.contains_object(&tree_id));
+    // The pack with the re-written duplicate is still valid
+    let written_content = packfile_writer.into_write();
+    let mut created_file = NamedTempFile::new()?;
+    created_file.write_all(written_content.as_ref())?;
+    let opened_packfile = gix_pack::data::File::at(created_file.path(), gix_hash::Kind::Sha1);
+    let opened_packfile = opened_packfile.expect("Expected successful opening of packfile");
+    assert_eq!(opened_packfile.num_objects(), num_entries);
+    assert_eq!(opened_packfile.data_len(), size as usize);
+    let checksum_from_file = opened_packfile
+        .verify_checksum(gix_features::progress::Discard, &AtomicBool::new(false))
+        .expect("Expected successful checksum computation");
+    assert_eq!(checksum, checksum_from_file);
+    Ok(())
+}
+
+#[fbinit::test]
+async fn validate_packfile_generation_to_async_writer() -> anyhow::Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    let concurrency = 100;
+    let objects_stream = get_objects_stream(false).await?;
+    // A small duplex stream stands in for a tokio socket, forcing the pack
+    // to be streamed out under backpressure instead of buffered.
+    let (client, mut server) = tokio::io::duplex(64);
+    let reader = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        server.read_to_end(&mut buf).await.expect("read");
+        buf
+    });
+    let mut packfile_writer = PackfileWriter::new(client, 3, concurrency, DeltaForm::RefAndOffset);
+    packfile_writer
+        .write(objects_stream)
+        .await
+        .expect("Expected successful write of objects to packfile");
+    let checksum = packfile_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    let (num_entries, size) = (packfile_writer.num_entries, packfile_writer.size);
+    // Close the writer half so the reader sees EOF.
+    drop(packfile_writer);
+    let written_content = reader.await?;
+    // Write the packfile to disk
+    let mut created_file = NamedTempFile::new()?;
+    created_file.write_all(written_content.as_ref())?;
+    // Open the written packfile
+    let opened_packfile = gix_pack::data::File::at(created_file.path(), gix_hash::Kind::Sha1);
+    let opened_packfile = opened_packfile.expect("Expected successful opening of packfile");
+    // Validate the packfile is the same as one written to an in-memory buffer
+    assert_eq!(opened_packfile.num_objects(), num_entries);
+    assert_eq!(opened_packfile.data_len(), size as usize);
+    let checksum_from_file = opened_packfile
+        .verify_checksum(gix_features::progress::Discard, &AtomicBool::new(false))
+        .expect("Expected successful checksum computation");
+    assert_eq!(checksum, checksum_from_file);
+    Ok(())
+}
+
+#[fbinit::test]
+async fn validate_streamed_packfile_generation() -> anyhow::Result<()> {
+    let concurrency = 100;
+    // Create a large blob spanning many streaming buffers
+    let data: Vec<u8> = (0..1_000_000u32).map(|i| (i * 31 % 251) as u8).collect();
+    let blob_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Blob(gix_object::Blob {
+        data: data.clone(),
+    }))?);
+    let expected_hash = BaseObject::new(blob_bytes.clone())?.hash().to_owned();
+    // Write the blob through the buffered path
+    let mut buffered_writer =
+        PackfileWriter::new(Vec::new(), 1, concurrency, DeltaForm::RefAndOffset);
+    buffered_writer
+        .write(stream::iter(vec![PackfileItem::new_base(blob_bytes)]))
+        .await
+        .expect("Expected successful write of objects to packfile");
+    buffered_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    let buffered_content = buffered_writer.into_write();
+    // Write the same blob through the streaming path
+    let mut streaming_writer =
+        PackfileWriter::new(Vec::new(), 1, concurrency, DeltaForm::RefAndOffset);
+    let streamed_hash = streaming_writer
+        .write_object_streaming(gix_object::Kind::Blob, data.len() as u64, data.as_slice())
+        .await
+        .expect("Expected successful streamed write of object to packfile");
+    streaming_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    let streamed_content = streaming_writer.into_write();
+    // The streamed path computes the same object id and produces a
+    // byte-identical packfile
+    assert_eq!(streamed_hash, expected_hash);
+    assert_eq!(streamed_content, buffered_content);
+    Ok(())
+}
+
 #[fbinit::test]
 async fn validate_roundtrip_packfile_generation() -> anyhow::Result<()> {
     // Create a few Git objects
@@ -349,6 +682,96 @@ async fn validate_delta_packfile_generation() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn validate_object_type_counts() -> anyhow::Result<()> {
+    let concurrency = 100;
+    let mut packfile_writer =
+        PackfileWriter::new(Vec::new(), 4, concurrency, DeltaForm::RefAndOffset);
+    // Create one Git object of each kind
+    let commit_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Commit(
+        gix_object::Commit {
+            tree: ObjectId::empty_tree(gix_hash::Kind::Sha1),
+            parents: Default::default(),
+            author: Default::default(),
+            committer: Default::default(),
+            encoding: None,
+            message: "Commit pointing to the empty tree".into(),
+            extra_headers: Vec::new(),
+        },
+    ))?);
+    let tree_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Tree(gix_object::Tree {
+        entries: vec![gix_object::tree::Entry {
+            mode: gix_object::tree::EntryMode::Blob,
+            filename: "JustAFile.txt".into(),
+            oid: ObjectId::empty_blob(gix_hash::Kind::Sha1),
+        }],
+    }))?);
+    let blob_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Blob(gix_object::Blob {
+        data: "Some file content".as_bytes().to_vec(),
+    }))?);
+    let tag_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Tag(Tag {
+        target: ObjectId::empty_tree(gix_hash::Kind::Sha1),
+        target_kind: gix_object::Kind::Tree,
+        name: "TreeTag".into(),
+        tagger: None,
+        message: "Tag pointing to a tree".into(),
+        pgp_signature: None,
+    }))?);
+    let objects_stream = stream::iter(vec![
+        PackfileItem::new_base(commit_bytes),
+        PackfileItem::new_base(tree_bytes),
+        PackfileItem::new_base(blob_bytes),
+        PackfileItem::new_base(tag_bytes),
+    ]);
+    // Validate we are able to write the objects to the packfile without errors
+    packfile_writer
+        .write(objects_stream)
+        .await
+        .expect("Expected successful write of objects to packfile");
+    // Validate we are able to finish writing to the packfile and generate the final checksum
+    packfile_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    // Validate each kind of written object was counted exactly once
+    assert_eq!(
+        packfile_writer.object_type_counts(),
+        ObjectTypeCounts {
+            commits: 1,
+            trees: 1,
+            blobs: 1,
+            tags: 1,
+            deltas: 0,
+        }
+    );
+
+    // Write a pack that includes a deltified object
+    let objects_stream = get_objects_stream(true).await?;
+    let mut packfile_writer =
+        PackfileWriter::new(Vec::new(), 4, concurrency, DeltaForm::OnlyOffset);
+    packfile_writer
+        .write(objects_stream)
+        .await
+        .expect("Expected successful write of objects to packfile");
+    packfile_writer
+        .finish()
+        .await
+        .expect("Expected successful checksum computation for packfile");
+    // The deltified object is counted as a delta instead of its underlying
+    // kind, which is not known without resolving the delta chain
+    assert_eq!(
+        packfile_writer.object_type_counts(),
+        ObjectTypeCounts {
+            commits: 0,
+            trees: 1,
+            blobs: 1,
+            tags: 1,
+            deltas: 1,
+        }
+    );
+    Ok(())
+}
+
 #[fbinit::test]
 async fn validate_basic_bundle_generation() -> anyhow::Result<()> {
     // Create a few Git objects
@@ -362,7 +785,9 @@ async fn validate_basic_bundle_generation() -> anyhow::Result<()> {
     let mut bundle_writer = BundleWriter::new_with_header(
         Vec::new(),
         refs,
-        Vec::new(),
+        // HEAD points at the empty tree which is not part of the written
+        // objects, so declare it as a prerequisite.
+        vec![ObjectId::empty_tree(gix_hash::Kind::Sha1)],
         3,
         concurrency,
         DeltaForm::RefAndOffset,
@@ -382,6 +807,72 @@ async fn validate_basic_bundle_generation() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn validate_concurrent_bundle_generation() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    let mut outputs = Vec::new();
+    for concurrency in [1, 4] {
+        let refs = vec![(
+            "HEAD".to_owned(),
+            ObjectId::empty_tree(gix_hash::Kind::Sha1),
+        )];
+        let mut bundle_writer = BundleWriter::new_with_header(
+            Vec::new(),
+            refs,
+            // HEAD points at the empty tree which is not part of the written
+            // objects, so declare it as a prerequisite.
+            vec![ObjectId::empty_tree(gix_hash::Kind::Sha1)],
+            3,
+            100,
+            DeltaForm::RefAndOffset,
+        )
+        .await
+        .expect("Expected successful creation of BundleWriter");
+        let tag_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Tag(Tag {
+            target: ObjectId::empty_tree(gix_hash::Kind::Sha1),
+            target_kind: gix_object::Kind::Tree,
+            name: "TreeTag".into(),
+            tagger: None,
+            message: "Tag pointing to a tree".into(),
+            pgp_signature: None,
+        }))?);
+        let blob_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Blob(gix_object::Blob {
+            data: "Some file content".as_bytes().to_vec(),
+        }))?);
+        let tree_bytes = Bytes::from(to_vec_bytes(&gix_object::Object::Tree(gix_object::Tree {
+            entries: vec![gix_object::tree::Entry {
+                mode: gix_object::tree::EntryMode::Blob,
+                filename: "JustAFile.txt".into(),
+                oid: ObjectId::empty_blob(gix_hash::Kind::Sha1),
+            }],
+        }))?);
+        // Later futures resolve sooner, so unordered completion would flip
+        // the object order. write_concurrent must still write in stream
+        // order.
+        let object_futures = vec![tag_bytes, blob_bytes, tree_bytes]
+            .into_iter()
+            .enumerate()
+            .map(|(index, bytes)| async move {
+                tokio::time::sleep(Duration::from_millis(30 - 10 * index as u64)).await;
+                PackfileItem::new_base(bytes)
+            });
+        bundle_writer
+            .write_concurrent(stream::iter(object_futures), concurrency)
+            .await
+            .expect("Expected successful write of objects to bundle");
+        bundle_writer
+            .finish()
+            .await
+            .expect("Expected successful finish of bundle creation");
+        outputs.push(bundle_writer.into_write());
+    }
+    // The bundle bytes (and hence the checksum) are identical regardless of
+    // the concurrency level.
+    assert_eq!(outputs[0], outputs[1]);
+    Ok(())
+}
+
 #[fbinit::test]
 async fn validate_staggered_bundle_generation() -> anyhow::Result<()> {
     let refs = vec![(
@@ -393,7 +884,9 @@ async fn validate_staggered_bundle_generation() -> anyhow::Result<()> {
     let mut bundle_writer = BundleWriter::new_with_header(
         Vec::new(),
         refs,
-        Vec::new(),
+        // HEAD points at the empty tree which is not part of the written
+        // objects, so declare it as a prerequisite.
+        vec![ObjectId::empty_tree(gix_hash::Kind::Sha1)],
         3,
         concurrency,
         DeltaForm::RefAndOffset,
@@ -438,6 +931,40 @@ async fn validate_staggered_bundle_generation() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[fbinit::test]
+async fn validate_bundle_with_dangling_ref() -> anyhow::Result<()> {
+    // Create a few Git objects
+    let objects_stream = get_objects_stream(false).await?;
+    // The empty tree is never part of the written objects and is not
+    // declared as a prerequisite.
+    let dangling = ObjectId::empty_tree(gix_hash::Kind::Sha1);
+    let refs = vec![("refs/heads/missing".to_owned(), dangling.clone())];
+    let concurrency = 100;
+    let mut bundle_writer = BundleWriter::new_with_header(
+        Vec::new(),
+        refs,
+        Vec::new(),
+        3,
+        concurrency,
+        DeltaForm::RefAndOffset,
+    )
+    .await
+    .expect("Expected successful creation of BundleWriter");
+    bundle_writer
+        .write(objects_stream)
+        .await
+        .expect("Expected successful write of objects to bundle.");
+    // Finishing the bundle must fail and name the missing object
+    let err = bundle_writer
+        .finish()
+        .await
+        .expect_err("Expected bundle with dangling ref to fail validation");
+    let message = format!("{}", err);
+    assert!(message.contains(&dangling.to_string()));
+    assert!(message.contains("refs/heads/missing"));
+    Ok(())
+}
+
 quickcheck! {
     fn git_packfile_base_item_thrift_roundtrip(entry: GitPackfileBaseItem) -> bool {
         let thrift_entry: thrift::GitPackfileBaseItem = entry.clone().into();
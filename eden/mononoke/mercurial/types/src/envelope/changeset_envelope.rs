@@ -84,6 +84,21 @@ impl HgChangesetEnvelope {
         Self::from_thrift(thrift_tc)
     }
 
+    /// Like `from_blob`, but refuses to deserialize blobs larger than
+    /// `limit` bytes so callers can distinguish "too big" from "corrupt".
+    pub fn from_blob_with_limit(blob: HgEnvelopeBlob, limit: u64) -> Result<Self> {
+        let size = blob.0.len() as u64;
+        if size > limit {
+            return Err(MononokeHgError::BlobTooLarge {
+                id: "HgChangesetEnvelope".into(),
+                size,
+                limit,
+            }
+            .into());
+        }
+        Self::from_blob(blob)
+    }
+
     /// The ID for this changeset, as recorded by Mercurial. This is expected to match the
     /// actual hash computed from the contents.
     #[inline]
@@ -101,6 +101,21 @@ impl HgFileEnvelope {
         Self::from_thrift(thrift_tc)
     }
 
+    /// Like `from_blob`, but refuses to deserialize blobs larger than
+    /// `limit` bytes so callers can distinguish "too big" from "corrupt".
+    pub fn from_blob_with_limit(blob: HgEnvelopeBlob, limit: u64) -> Result<Self> {
+        let size = blob.0.len() as u64;
+        if size > limit {
+            return Err(MononokeHgError::BlobTooLarge {
+                id: "HgFileEnvelope".into(),
+                size,
+                limit,
+            }
+            .into());
+        }
+        Self::from_blob(blob)
+    }
+
     /// The ID for this file version.
     #[inline]
     pub fn node_id(&self) -> HgFileNodeId {
@@ -214,6 +229,34 @@ mod test {
         }
     }
 
+    #[test]
+    fn blob_too_large() {
+        let thrift_fe = thrift::HgFileEnvelope {
+            node_id: thrift::HgNodeHash(thrift::id::Sha1(vec![1; 20].into())),
+            p1: None,
+            p2: None,
+            content_id: Some(thrift::id::ContentId(thrift::id::Id::Blake2(
+                thrift::id::Blake2(vec![3; 32].into()),
+            ))),
+            content_size: 42,
+            metadata: Some(vec![]),
+        };
+        let fe = HgFileEnvelope::from_thrift(thrift_fe).expect("valid envelope");
+        let blob = fe.into_blob();
+        let blob_size = blob.0.len() as u64;
+
+        let err = HgFileEnvelope::from_blob_with_limit(blob, 4)
+            .expect_err("unexpected OK -- blob exceeds limit");
+        match err.downcast::<MononokeHgError>() {
+            Ok(MononokeHgError::BlobTooLarge { id, size, limit }) => {
+                assert_eq!(id, "HgFileEnvelope");
+                assert_eq!(size, blob_size);
+                assert_eq!(limit, 4);
+            }
+            other => panic!("expected BlobTooLarge, got {:?}", other),
+        }
+    }
+
     #[test]
     fn bad_thrift() {
         let mut thrift_fe = thrift::HgFileEnvelope {
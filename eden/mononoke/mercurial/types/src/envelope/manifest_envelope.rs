@@ -87,6 +87,21 @@ impl HgManifestEnvelope {
         Self::from_thrift(thrift_tc)
     }
 
+    /// Like `from_blob`, but refuses to deserialize blobs larger than
+    /// `limit` bytes so callers can distinguish "too big" from "corrupt".
+    pub fn from_blob_with_limit(blob: HgEnvelopeBlob, limit: u64) -> Result<Self> {
+        let size = blob.0.len() as u64;
+        if size > limit {
+            return Err(MononokeHgError::BlobTooLarge {
+                id: "HgManifestEnvelope".into(),
+                size,
+                limit,
+            }
+            .into());
+        }
+        Self::from_blob(blob)
+    }
+
     /// The ID for this manifest, as recorded by Mercurial. This might or might not match the
     /// actual hash computed from the contents.
     #[inline]
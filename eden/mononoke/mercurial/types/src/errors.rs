@@ -11,12 +11,53 @@ use thiserror::Error;
 pub enum MononokeHgError {
     #[error("invalid sha-1 input: {0}")]
     InvalidSha1Input(String),
+    #[error("invalid sha-1 input: expected 40 hex chars, got {got}")]
+    Sha1WrongLength { got: usize },
+    #[error("invalid sha-1 input: non-hex byte {byte:#04x} at position {at}")]
+    Sha1NonHex { at: usize, byte: u8 },
     #[error("invalid fragment list: {0}")]
     InvalidFragmentList(String),
     #[error("invalid Thrift structure '{0}': {1}")]
     InvalidThrift(String, String),
+    #[error(
+        "invalid Thrift structure '{structure}'{}",
+        .field.as_ref().map_or_else(String::new, |f| format!(", field '{}'", f))
+    )]
+    InvalidThriftDetailed {
+        structure: String,
+        field: Option<String>,
+        #[source]
+        source: anyhow::Error,
+    },
     #[error("error while deserializing blob for '{0}'")]
     BlobDeserializeError(String),
+    #[error("blob for '{id}' is too large: {size} bytes exceeds the limit of {limit} bytes")]
+    BlobTooLarge { id: String, size: u64, limit: u64 },
     #[error("imposssible to parse unknown rev flags")]
     UnknownRevFlags,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_thrift_detailed_message() {
+        let err = MononokeHgError::InvalidThriftDetailed {
+            structure: "HgFileEnvelope".into(),
+            field: Some("content_id".into()),
+            source: anyhow::Error::msg("missing content id field"),
+        };
+        assert_eq!(
+            err.to_string(),
+            "invalid Thrift structure 'HgFileEnvelope', field 'content_id'"
+        );
+
+        let err = MononokeHgError::InvalidThriftDetailed {
+            structure: "HgFileEnvelope".into(),
+            field: None,
+            source: anyhow::Error::msg("truncated"),
+        };
+        assert_eq!(err.to_string(), "invalid Thrift structure 'HgFileEnvelope'");
+    }
+}
@@ -27,6 +27,7 @@ use sql::mysql;
 /// Equivalent to HgNodeHash;
 use types::HgId;
 
+use crate::errors::MononokeHgError;
 use crate::manifest::Type;
 use crate::thrift;
 use crate::RepoPath;
@@ -34,6 +35,23 @@ use crate::RepoPath;
 pub const NULL_HASH: HgNodeHash = HgNodeHash(sha1_hash::NULL);
 pub const NULL_CSID: HgChangesetId = HgChangesetId(NULL_HASH);
 
+/// Parse a hex-encoded SHA-1, classifying failures so callers can tell a
+/// wrong-length input apart from one containing non-hex characters.
+fn parse_sha1_hex(s: &str) -> Result<Sha1> {
+    if s.len() != sha1_hash::SHA1_HASH_LENGTH_HEX {
+        return Err(MononokeHgError::Sha1WrongLength { got: s.len() }.into());
+    }
+    if let Some((at, &byte)) = s
+        .as_bytes()
+        .iter()
+        .enumerate()
+        .find(|&(_, b)| !b.is_ascii_hexdigit())
+    {
+        return Err(MononokeHgError::Sha1NonHex { at, byte }.into());
+    }
+    Sha1::from_str(s)
+}
+
 /// This structure represents Sha1 based hashes that are used in Mercurial, but the Sha1
 /// structure is private outside this crate to keep it an implementation detail.
 /// This is why the main constructors to create this structure are from_bytes and from_ascii_str
@@ -76,7 +94,7 @@ impl HgNodeHash {
 
     #[inline]
     pub fn from_ascii_str(s: &AsciiStr) -> Result<Self> {
-        Sha1::from_ascii_str(s).map(HgNodeHash)
+        parse_sha1_hex(s.as_str()).map(HgNodeHash)
     }
 
     /// Returns a 40 hex digits representation of the sha1 hash
@@ -199,7 +217,7 @@ impl FromStr for HgNodeHash {
     type Err = <Sha1 as FromStr>::Err;
 
     fn from_str(s: &str) -> result::Result<Self, Self::Err> {
-        Sha1::from_str(s).map(HgNodeHash)
+        parse_sha1_hex(s).map(HgNodeHash)
     }
 }
 
@@ -678,3 +696,31 @@ impl_hash!(HgManifestId);
 impl_hash!(HgAugmentedManifestId);
 impl_hash!(HgFileNodeId);
 impl_hash!(HgEntryId);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sha1_wrong_length() {
+        let err = HgNodeHash::from_str("abcd").expect_err("unexpected OK -- too short");
+        match err.downcast::<MononokeHgError>() {
+            Ok(MononokeHgError::Sha1WrongLength { got }) => assert_eq!(got, 4),
+            other => panic!("expected Sha1WrongLength, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sha1_non_hex() {
+        let mut s = "a".repeat(40);
+        s.replace_range(7..8, "g");
+        let err = HgNodeHash::from_str(&s).expect_err("unexpected OK -- non-hex byte");
+        match err.downcast::<MononokeHgError>() {
+            Ok(MononokeHgError::Sha1NonHex { at, byte }) => {
+                assert_eq!(at, 7);
+                assert_eq!(byte, b'g');
+            }
+            other => panic!("expected Sha1NonHex, got {:?}", other),
+        }
+    }
+}
@@ -11,6 +11,7 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::path::Path;
 use std::str;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -229,6 +230,7 @@ fn parse_with_repo_definition(
         zelos_config,
         bookmark_name_for_objects_count,
         default_objects_count,
+        max_concurrent_requests,
         ..
     } = named_repo_config;
 
@@ -347,6 +349,11 @@ fn parse_with_repo_definition(
     let git_concurrency = git_concurrency.convert()?;
     let metadata_logger_config = metadata_logger_config.convert()?.unwrap_or_default();
     let zelos_config = zelos_config.convert()?;
+    // Zero means unbounded.
+    let max_concurrent_requests = max_concurrent_requests
+        .filter(|limit| *limit > 0)
+        .map(|limit| limit.try_into())
+        .transpose()?;
 
     Ok(RepoConfig {
         enabled,
@@ -395,6 +402,7 @@ fn parse_with_repo_definition(
         zelos_config,
         bookmark_name_for_objects_count,
         default_objects_count,
+        max_concurrent_requests,
     })
 }
 
@@ -444,6 +452,23 @@ fn parse_common_config(
         .map(|bound| bound.try_into())
         .transpose()?;
     let edenapi_dumper_scuba_table = common.edenapi_dumper_scuba_table;
+    let connection_drain_timeout = common
+        .connection_drain_timeout_secs
+        .map(|secs| -> Result<Duration> { Ok(Duration::from_secs(secs.try_into()?)) })
+        .transpose()?;
+    let max_concurrent_connections = common
+        .max_concurrent_connections
+        .map(|limit| limit.try_into())
+        .transpose()?;
+    // Zero disables the idle timeout.
+    let connection_idle_timeout = common
+        .connection_idle_timeout_secs
+        .filter(|secs| *secs > 0)
+        .map(|secs| -> Result<Duration> { Ok(Duration::from_secs(secs.try_into()?)) })
+        .transpose()?;
+    // Zero falls back to the default backlog.
+    let listen_backlog = common.listen_backlog.filter(|backlog| *backlog > 0);
+    let reuse_address = common.reuse_address.unwrap_or_default();
 
     let censored_scuba_params = CensoredScubaParams {
         table: scuba_censored_table,
@@ -485,6 +510,11 @@ fn parse_common_config(
         internal_identity,
         git_memory_upper_bound,
         edenapi_dumper_scuba_table,
+        connection_drain_timeout,
+        max_concurrent_connections,
+        connection_idle_timeout,
+        listen_backlog,
+        reuse_address,
     })
 }
 
@@ -977,6 +1007,11 @@ mod test {
             trusted_parties_hipster_tier="tier1"
             git_memory_upper_bound=100
             edenapi_dumper_scuba_table="dumped_requests"
+            connection_drain_timeout_secs=30
+            max_concurrent_connections=1000
+            connection_idle_timeout_secs=300
+            listen_backlog=2048
+            reuse_address=true
 
             [internal_identity]
             identity_type = "SERVICE_IDENTITY"
@@ -1367,6 +1402,7 @@ mod test {
                 zelos_config: None,
                 bookmark_name_for_objects_count: None,
                 default_objects_count: None,
+                max_concurrent_requests: None,
             },
         );
 
@@ -1447,6 +1483,7 @@ mod test {
                 zelos_config: None,
                 bookmark_name_for_objects_count: None,
                 default_objects_count: None,
+                max_concurrent_requests: None,
             },
         );
         assert_eq!(
@@ -1475,6 +1512,11 @@ mod test {
                 },
                 git_memory_upper_bound: Some(100),
                 edenapi_dumper_scuba_table: Some("dumped_requests".to_string()),
+                connection_drain_timeout: Some(Duration::from_secs(30)),
+                max_concurrent_connections: Some(1000),
+                connection_idle_timeout: Some(Duration::from_secs(300)),
+                listen_backlog: Some(2048),
+                reuse_address: true,
             }
         );
         assert_eq!(
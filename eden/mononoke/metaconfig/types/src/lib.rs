@@ -137,6 +137,23 @@ pub struct CommonConfig {
     pub git_memory_upper_bound: Option<u64>,
     /// Scuba table to dump edenapi requests to (for replay).
     pub edenapi_dumper_scuba_table: Option<String>,
+    /// How long to wait for in-flight connections to finish after the server
+    /// stops accepting new ones during shutdown. Connections still open after
+    /// the timeout are force-closed. `None` means shutdown does not wait.
+    pub connection_drain_timeout: Option<Duration>,
+    /// Maximum number of simultaneously-served connections. New connections
+    /// beyond the limit wait for a slot to free up. `None` or zero means
+    /// unlimited.
+    pub max_concurrent_connections: Option<usize>,
+    /// Close a connection whose client has sent no bytes for this long.
+    /// `None` disables the idle timeout.
+    pub connection_idle_timeout: Option<Duration>,
+    /// Accept backlog applied when binding the listening socket. `None`
+    /// uses the default (1024).
+    pub listen_backlog: Option<i32>,
+    /// Set SO_REUSEADDR on the listening socket so rapid restarts do not
+    /// fail with "address already in use".
+    pub reuse_address: bool,
 }
 
 /// Configuration for logging of censored blobstore accesses
@@ -248,6 +265,11 @@ pub struct RepoConfig {
     pub bookmark_name_for_objects_count: Option<String>,
     /// Default value for the objects count metric if it cannot be determined via TreeInfo.
     pub default_objects_count: Option<i64>,
+    /// Maximum number of simultaneously-served requests for this repo.
+    /// Requests beyond the limit wait for a slot to free up, so one busy
+    /// repo cannot starve others sharing the process. `None` means
+    /// unbounded.
+    pub max_concurrent_requests: Option<usize>,
 }
 
 /// Config determining if the repo is deep sharded in the context of a service.
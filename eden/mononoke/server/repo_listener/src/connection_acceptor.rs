@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashSet;
 use std::fs::File;
 use std::io;
 use std::io::Write;
@@ -14,11 +15,13 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::RwLock;
 use std::time::Duration;
 
 use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use bytes::Bytes;
 use cached_config::ConfigStore;
 use connection_security_checker::ConnectionSecurityChecker;
@@ -70,7 +73,9 @@ use stats::prelude::*;
 use tokio::io::AsyncRead;
 use tokio::io::AsyncWrite;
 use tokio::net::TcpListener;
-use tokio::net::TcpStream;
+use tokio::net::TcpSocket;
+use tokio::net::UnixListener;
+use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
 use tokio_openssl::SslStream;
 use tokio_util::codec::FramedRead;
@@ -78,6 +83,9 @@ use tokio_util::codec::FramedWrite;
 
 use crate::errors::ErrorKind;
 use crate::http_service::MononokeHttpService;
+use crate::metrics::MetricsSink;
+use crate::repo_handlers::RepoConcurrencyLimiter;
+use crate::repo_handlers::RepoFilter;
 use crate::request_handler::create_conn_logger;
 use crate::request_handler::request_handler;
 use crate::wireproto_sink::WireprotoSink;
@@ -87,14 +95,102 @@ define_stats! {
     http_accepted: timeseries(Sum),
 }
 
+/// Runtime-mutable set of client identities (rendered as `TYPE:data`) whose
+/// connections are refused before a handler is dispatched. Shared so an
+/// admin channel can add or remove entries while the server is running.
+pub type ClientIdentityDenylist = Arc<RwLock<HashSet<String>>>;
+
 pub trait MononokeStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static {}
 
 impl<T> MononokeStream for T where T: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static {}
 
+/// Where the wireproto service listens. TCP is the default; unix domain
+/// sockets suit deployments where exposing a port is awkward. TLS is
+/// applied on both transports.
+#[derive(Clone, Debug)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl std::str::FromStr for ListenAddr {
+    type Err = Error;
+
+    /// Absolute paths are unix sockets; everything else must parse as a
+    /// `host:port` TCP address.
+    fn from_str(sockname: &str) -> Result<Self> {
+        if sockname.starts_with('/') {
+            Ok(ListenAddr::Unix(PathBuf::from(sockname)))
+        } else {
+            Ok(ListenAddr::Tcp(sockname.parse().with_context(|| {
+                format!("could not parse '{}'", sockname)
+            })?))
+        }
+    }
+}
+
+impl From<SocketAddr> for ListenAddr {
+    fn from(addr: SocketAddr) -> Self {
+        ListenAddr::Tcp(addr)
+    }
+}
+
+impl From<PathBuf> for ListenAddr {
+    fn from(path: PathBuf) -> Self {
+        ListenAddr::Unix(path)
+    }
+}
+
+impl std::fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "{}", addr),
+            ListenAddr::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A bound listening socket for either transport.
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Listener {
+    async fn accept(&self) -> io::Result<(Box<dyn MononokeStream>, SocketAddr)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Box::new(stream), addr))
+            }
+            Listener::Unix(listener) => {
+                let (stream, _addr) = listener.accept().await?;
+                // Unix peers have no IP address; report a loopback
+                // placeholder since downstream logging and scuba columns
+                // expect a socket address.
+                Ok((Box::new(stream), SocketAddr::from(([127, 0, 0, 1], 0))))
+            }
+        }
+    }
+
+    fn bound_addr(&self) -> Result<String> {
+        match self {
+            Listener::Tcp(listener) => Ok(listener.local_addr()?.to_string()),
+            Listener::Unix(listener) => Ok(match listener.local_addr()?.as_pathname() {
+                Some(path) => path.display().to_string(),
+                None => "unnamed unix socket".to_string(),
+            }),
+        }
+    }
+}
+
 const KEEP_ALIVE_INTERVAL: Duration = Duration::from_millis(5000);
 const CHUNK_SIZE: usize = 10000;
 lazy_static! {
     static ref OPEN_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+    static ref CONNECTION_ABORT_HANDLES: std::sync::Mutex<std::collections::HashMap<usize, AbortHandle>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+    static ref NEXT_CONNECTION_ID: AtomicUsize = AtomicUsize::new(0);
 }
 
 pub async fn wait_for_connections_closed(logger: &Logger) {
@@ -109,15 +205,51 @@ pub async fn wait_for_connections_closed(logger: &Logger) {
     }
 }
 
+/// Wait for in-flight connections to finish, up to `timeout`. Connections
+/// still open when the timeout expires are force-closed. `None` means don't
+/// wait at all, preserving the old immediate-return shutdown behavior.
+pub async fn drain_connections(logger: &Logger, timeout: Option<Duration>) {
+    let deadline = match timeout {
+        Some(timeout) => tokio::time::Instant::now() + timeout,
+        None => return,
+    };
+
+    loop {
+        let conns = OPEN_CONNECTIONS.load(Ordering::Relaxed);
+        if conns == 0 {
+            break;
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            warn!(
+                logger,
+                "Drain timeout expired, force-closing {} connections", conns
+            );
+            let handles = CONNECTION_ABORT_HANDLES
+                .lock()
+                .expect("lock poisoned")
+                .drain()
+                .collect::<Vec<_>>();
+            for (_id, handle) in handles {
+                handle.abort();
+            }
+            break;
+        }
+
+        info!(logger, "Waiting for {} connections to drain", conns);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
 pub async fn connection_acceptor(
     fb: FacebookInit,
     configs: Arc<MononokeConfigs>,
     common_config: CommonConfig,
-    sockname: String,
+    listen_addr: ListenAddr,
     service: ReadyFlagService,
     root_log: Logger,
     mononoke: Arc<Mononoke>,
-    tls_acceptor: SslAcceptor,
+    tls_acceptor: Arc<ArcSwap<SslAcceptor>>,
     terminate_process: oneshot::Receiver<()>,
     rate_limiter: Option<RateLimitEnvironment>,
     scribe: Scribe,
@@ -130,16 +262,25 @@ pub async fn connection_acceptor(
     acl_provider: &dyn AclProvider,
     readonly: bool,
     mtls_disabled: bool,
+    metrics_sink: Arc<dyn MetricsSink>,
+    identity_denylist: ClientIdentityDenylist,
+    repo_filter: Option<RepoFilter>,
 ) -> Result<()> {
     let enable_http_control_api = common_config.enable_http_control_api;
 
     let security_checker = ConnectionSecurityChecker::new(acl_provider, &common_config).await?;
-    let addr: SocketAddr = sockname
-        .parse()
-        .with_context(|| format!("could not parse '{}'", sockname))?;
-    let listener = TcpListener::bind(&addr)
-        .await
-        .with_context(|| format!("could not bind mononoke on '{}'", sockname))?;
+    let listener = match &listen_addr {
+        ListenAddr::Tcp(addr) => bind_listener(
+            *addr,
+            common_config.listen_backlog,
+            common_config.reuse_address,
+        )
+        .map(Listener::Tcp),
+        ListenAddr::Unix(path) => UnixListener::bind(path)
+            .map(Listener::Unix)
+            .map_err(Error::from),
+    }
+    .with_context(|| format!("could not bind mononoke on '{}'", listen_addr))?;
 
     let mut terminate_process = terminate_process.fuse();
 
@@ -152,8 +293,9 @@ pub async fn connection_acceptor(
 
     // Now that we are listening and ready to accept connections, report that we are alive.
     service.set_ready();
+    let ready_service = service;
 
-    let bound_addr = listener.local_addr()?.to_string();
+    let bound_addr = listener.bound_addr()?;
     debug!(root_log, "server is listening on {}", bound_addr);
 
     // Write out the bound address if requested, this is helpful in tests when using automatic binding with :0
@@ -163,6 +305,9 @@ pub async fn connection_acceptor(
         writer.write_all(b"\n")?;
     }
 
+    let connection_limit = connection_limit_semaphore(common_config.max_concurrent_connections);
+    let repo_concurrency = Arc::new(RepoConcurrencyLimiter::new());
+
     let acceptor = Arc::new(Acceptor {
         fb,
         tls_acceptor,
@@ -182,18 +327,41 @@ pub async fn connection_acceptor(
         common_config,
         readonly,
         mtls_disabled,
+        connection_limit,
+        ready_service,
+        metrics_sink,
+        identity_denylist,
+        repo_filter,
+        repo_concurrency,
     });
 
     loop {
         select_biased! {
             _ = terminate_process => {
                 debug!(root_log, "Received shutdown handler, stop accepting connections...");
+                drain_connections(&root_log, acceptor.common_config.connection_drain_timeout).await;
                 return Ok(());
             },
             sock_tuple = listener.accept().fuse() => match sock_tuple {
                 Ok((stream, addr)) => {
                     let conn = PendingConnection { acceptor: acceptor.clone(), addr };
-                    let task = handle_connection(conn.clone(), stream);
+                    let task = {
+                        let conn = conn.clone();
+                        async move {
+                            // When at the connection limit, park until a slot
+                            // frees up.
+                            let _permit = match conn.acceptor.connection_limit.clone() {
+                                Some(semaphore) => Some(
+                                    semaphore
+                                        .acquire_owned()
+                                        .await
+                                        .context("Connection limit semaphore closed")?,
+                                ),
+                                None => None,
+                            };
+                            handle_connection(conn.clone(), stream).await
+                        }
+                    };
                     conn.spawn_task(task, "Failed to handle_connection");
                 }
                 Err(err) => {
@@ -204,10 +372,46 @@ pub async fn connection_acceptor(
     }
 }
 
+/// Accept backlog used when the config doesn't override it, matching what
+/// `TcpListener::bind` uses.
+const DEFAULT_LISTEN_BACKLOG: u32 = 1024;
+
+/// Bind the listening socket. `backlog` overrides the accept backlog;
+/// `reuse_address` sets SO_REUSEADDR so rapid restarts don't fail with
+/// "address already in use". The defaults (`None`, `false`) preserve the
+/// plain `TcpListener::bind` behavior.
+fn bind_listener(
+    addr: SocketAddr,
+    backlog: Option<i32>,
+    reuse_address: bool,
+) -> Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    if reuse_address {
+        socket.set_reuseaddr(true)?;
+    }
+    socket.bind(addr)?;
+    let listener = socket.listen(backlog.map_or(DEFAULT_LISTEN_BACKLOG, |backlog| backlog as u32))?;
+    Ok(listener)
+}
+
+/// Build the semaphore bounding simultaneously-served connections. Zero or
+/// unset means unlimited.
+fn connection_limit_semaphore(max_concurrent_connections: Option<usize>) -> Option<Arc<Semaphore>> {
+    max_concurrent_connections
+        .filter(|limit| *limit > 0)
+        .map(|limit| Arc::new(Semaphore::new(limit)))
+}
+
 /// Our environment for accepting connections.
 pub struct Acceptor {
     pub fb: FacebookInit,
-    pub tls_acceptor: SslAcceptor,
+    /// The TLS acceptor used for new connections. Swapping in a rotated
+    /// acceptor is atomic and lock-free; connections that already completed
+    /// their handshake keep their existing TLS context.
+    pub tls_acceptor: Arc<ArcSwap<SslAcceptor>>,
     pub mononoke: Arc<Mononoke>,
     pub security_checker: ConnectionSecurityChecker,
     pub rate_limiter: Option<RateLimitEnvironment>,
@@ -224,6 +428,16 @@ pub struct Acceptor {
     pub common_config: CommonConfig,
     pub readonly: bool,
     pub mtls_disabled: bool,
+    pub connection_limit: Option<Arc<Semaphore>>,
+    pub ready_service: ReadyFlagService,
+    /// Sink receiving one structured metric per completed wireproto request.
+    pub metrics_sink: Arc<dyn MetricsSink>,
+    /// Client identities refused before dispatching to a handler.
+    pub identity_denylist: ClientIdentityDenylist,
+    /// Optional predicate restricting which repos this instance serves.
+    pub repo_filter: Option<RepoFilter>,
+    /// Per-repo request slots sized from `max_concurrent_requests`.
+    pub repo_concurrency: Arc<RepoConcurrencyLimiter>,
 }
 
 /// Details for a socket we've just opened.
@@ -253,25 +467,47 @@ impl PendingConnection {
 
         OPEN_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
 
+        // Register an abort handle so shutdown can force-close this
+        // connection if it outlives the drain timeout.
+        let conn_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+        let (task, abort_handle) = futures::future::abortable(task);
+        CONNECTION_ABORT_HANDLES
+            .lock()
+            .expect("lock poisoned")
+            .insert(conn_id, abort_handle);
+
         tokio::task::spawn(async move {
             let logger = &this.acceptor.logger;
             let res = task
                 .on_cancel(|| warn!(logger, "connection to {} was cancelled", this.addr))
-                .await
-                .context(label)
-                .with_context(|| format!("Failed to handle connection to {}", this.addr));
+                .await;
 
-            if let Err(e) = res {
-                error!(logger, "connection_acceptor error: {:#}", e);
+            match res {
+                Ok(res) => {
+                    let res = res
+                        .context(label)
+                        .with_context(|| format!("Failed to handle connection to {}", this.addr));
+                    if let Err(e) = res {
+                        error!(logger, "connection_acceptor error: {:#}", e);
+                    }
+                }
+                Err(futures_util::future::Aborted) => {
+                    warn!(logger, "connection to {} was force-closed", this.addr);
+                }
             }
 
+            CONNECTION_ABORT_HANDLES
+                .lock()
+                .expect("lock poisoned")
+                .remove(&conn_id);
             OPEN_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
         });
     }
 }
 
-async fn handle_connection(conn: PendingConnection, sock: TcpStream) -> Result<()> {
-    let ssl = Ssl::new(conn.acceptor.tls_acceptor.context()).context("Error creating Ssl")?;
+async fn handle_connection(conn: PendingConnection, sock: impl MononokeStream) -> Result<()> {
+    let tls_acceptor = conn.acceptor.tls_acceptor.load_full();
+    let ssl = Ssl::new(tls_acceptor.context()).context("Error creating Ssl")?;
     let ssl_socket = SslStream::new(ssl, sock).context("Error creating SslStream")?;
     let mut ssl_socket = Box::pin(ssl_socket);
 
@@ -306,6 +542,15 @@ async fn handle_connection(conn: PendingConnection, sock: TcpStream) -> Result<(
         }
     };
 
+    if let Some(denied) = denied_identity(&conn.pending.acceptor.identity_denylist, &conn.identities)
+    {
+        warn!(
+            conn.pending.acceptor.logger,
+            "Refusing connection from {}: identity {} is denylisted", conn.pending.addr, denied
+        );
+        return Err(ErrorKind::ClientIdentityDenied(denied).into());
+    }
+
     let ssl_socket = QuietShutdownStream::new(ssl_socket);
 
     handle_http(conn, ssl_socket)
@@ -315,6 +560,21 @@ async fn handle_connection(conn: PendingConnection, sock: TcpStream) -> Result<(
     Ok(())
 }
 
+/// Return the first denylisted identity of the connection, if any.
+fn denied_identity(
+    denylist: &ClientIdentityDenylist,
+    identities: &MononokeIdentitySet,
+) -> Option<String> {
+    let denylist = denylist.read().expect("lock poisoned");
+    if denylist.is_empty() {
+        return None;
+    }
+    identities
+        .iter()
+        .map(|identity| identity.to_string())
+        .find(|identity| denylist.contains(identity))
+}
+
 async fn handle_http<S: MononokeStream>(conn: AcceptedConnection, stream: S) -> Result<()> {
     STATS::http_accepted.add_value(1);
     let svc = MononokeHttpService::<S>::new(conn);
@@ -376,6 +636,9 @@ where
         conn.pending.acceptor.scribe.clone(),
         conn.pending.acceptor.qps.clone(),
         conn.pending.acceptor.readonly,
+        conn.pending.acceptor.metrics_sink.clone(),
+        conn.pending.acceptor.repo_filter.clone(),
+        conn.pending.acceptor.repo_concurrency.clone(),
     )
     .await
     .context("Failed to execute request_handler");
@@ -430,13 +693,18 @@ impl ChannelConn {
     {
         let FramedConn { rd, wr } = framed;
 
-        let stdin = Box::pin(rd.try_filter_map(|s| async move {
-            if s.stream() == IoStream::Stdin {
-                Ok(Some(s.data()))
-            } else {
-                Ok(None)
-            }
-        }));
+        let stdin: BoxStream<'static, Result<Bytes, io::Error>> =
+            Box::pin(rd.try_filter_map(|s| async move {
+                if s.stream() == IoStream::Stdin {
+                    Ok(Some(s.data()))
+                } else {
+                    Ok(None)
+                }
+            }));
+        let stdin = with_idle_timeout(
+            stdin,
+            conn.pending.acceptor.common_config.connection_idle_timeout,
+        );
 
         let (stdout, stderr, keep_alive, join_handle) = {
             let (otx, orx) = mpsc::channel(1);
@@ -539,6 +807,34 @@ impl ChannelConn {
     }
 }
 
+/// Wrap `stream` so that it fails with `io::ErrorKind::TimedOut` if the
+/// client sends no bytes for `timeout`. `None` disables the idle timeout.
+fn with_idle_timeout(
+    stream: BoxStream<'static, Result<Bytes, io::Error>>,
+    timeout: Option<Duration>,
+) -> BoxStream<'static, Result<Bytes, io::Error>> {
+    let timeout = match timeout {
+        Some(timeout) if !timeout.is_zero() => timeout,
+        _ => return stream,
+    };
+
+    Box::pin(stream::unfold(Some(stream), move |state| async move {
+        let mut stream = state?;
+        match tokio::time::timeout(timeout, stream.next()).await {
+            Ok(Some(item)) => Some((item, Some(stream))),
+            Ok(None) => None,
+            Err(_) => {
+                let err = io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("connection was idle for more than {:?}", timeout),
+                );
+                // Terminate the stream after reporting the timeout.
+                Some((Err(err), None))
+            }
+        }
+    }))
+}
+
 fn split_bytes_in_chunk<E>(blob: Bytes, chunksize: usize) -> impl Stream<Item = Result<Bytes, E>> {
     stream::try_unfold(blob, move |mut remain| async move {
         let len = remain.len();
@@ -550,3 +846,253 @@ fn split_bytes_in_chunk<E>(blob: Bytes, chunksize: usize) -> impl Stream<Item =
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_idle_timeout_drops_silent_client() {
+        // A client that connects and never sends anything.
+        let silent: BoxStream<'static, Result<Bytes, io::Error>> = Box::pin(stream::pending());
+
+        let mut stdin = with_idle_timeout(silent, Some(Duration::from_millis(50)));
+
+        let err = stdin
+            .next()
+            .await
+            .expect("expected timeout item")
+            .expect_err("expected timeout error");
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // The stream terminates after the timeout fires.
+        assert!(stdin.next().await.is_none());
+
+        // Zero disables the idle timeout entirely.
+        let items: Vec<Result<Bytes, io::Error>> = vec![Ok(Bytes::from_static(b"data"))];
+        let wrapped = with_idle_timeout(Box::pin(stream::iter(items)), Some(Duration::ZERO));
+        let collected: Vec<_> = wrapped.collect().await;
+        assert_eq!(collected.len(), 1);
+    }
+
+    #[test]
+    fn test_identity_denylist() {
+        let denylist = ClientIdentityDenylist::default();
+
+        let mut abusive = MononokeIdentitySet::new();
+        abusive.insert(MononokeIdentity::new("USER", "abusive"));
+        let mut friendly = MononokeIdentitySet::new();
+        friendly.insert(MononokeIdentity::new("USER", "friendly"));
+
+        // Nothing is denied while the list is empty.
+        assert_eq!(denied_identity(&denylist, &abusive), None);
+
+        denylist
+            .write()
+            .expect("lock poisoned")
+            .insert("USER:abusive".to_string());
+        assert_eq!(
+            denied_identity(&denylist, &abusive),
+            Some("USER:abusive".to_string())
+        );
+        // Other identities still proceed.
+        assert_eq!(denied_identity(&denylist, &friendly), None);
+
+        // Entries can be removed at runtime.
+        denylist
+            .write()
+            .expect("lock poisoned")
+            .remove("USER:abusive");
+        assert_eq!(denied_identity(&denylist, &abusive), None);
+    }
+
+    #[test]
+    fn test_tls_acceptor_swap() {
+        use openssl::ssl::SslMethod;
+
+        let make = || {
+            SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())
+                .expect("SslAcceptor builder")
+                .build()
+        };
+
+        let current = Arc::new(ArcSwap::from_pointee(make()));
+        let original = current.load_full();
+
+        let rotated = Arc::new(make());
+        current.store(rotated.clone());
+
+        // New connections pick up the rotated acceptor; handles taken before
+        // the swap are unaffected.
+        assert!(Arc::ptr_eq(&current.load_full(), &rotated));
+        assert!(!Arc::ptr_eq(&original, &rotated));
+    }
+
+    #[test]
+    fn test_connection_limit_semaphore() {
+        assert!(connection_limit_semaphore(None).is_none());
+        assert!(connection_limit_semaphore(Some(0)).is_none());
+        assert_eq!(
+            connection_limit_semaphore(Some(5))
+                .expect("semaphore")
+                .available_permits(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit_parks_second_connection() {
+        let semaphore = connection_limit_semaphore(Some(1)).expect("semaphore");
+
+        let first = semaphore.clone().acquire_owned().await.expect("permit");
+
+        let second = tokio::spawn({
+            let semaphore = semaphore.clone();
+            async move {
+                let _permit = semaphore.acquire_owned().await.expect("permit");
+            }
+        });
+
+        // The second connection is parked while the first holds the slot.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!second.is_finished());
+
+        drop(first);
+        second.await.expect("second connection");
+    }
+
+    #[tokio::test]
+    async fn test_tcp_tls_handshake() {
+        use openssl::asn1::Asn1Time;
+        use openssl::hash::MessageDigest;
+        use openssl::pkey::PKey;
+        use openssl::rsa::Rsa;
+        use openssl::ssl::SslConnector;
+        use openssl::ssl::SslMethod;
+        use openssl::ssl::SslVerifyMode;
+        use openssl::x509::X509NameBuilder;
+        use openssl::x509::X509;
+        use tokio::io::AsyncReadExt;
+        use tokio::io::AsyncWriteExt;
+
+        // Self-signed certificate for the test server.
+        let key = PKey::from_rsa(Rsa::generate(2048).expect("rsa")).expect("pkey");
+        let mut name = X509NameBuilder::new().expect("name builder");
+        name.append_entry_by_text("CN", "localhost").expect("cn");
+        let name = name.build();
+        let mut cert = X509::builder().expect("cert builder");
+        cert.set_version(2).expect("version");
+        cert.set_subject_name(&name).expect("subject");
+        cert.set_issuer_name(&name).expect("issuer");
+        cert.set_pubkey(&key).expect("pubkey");
+        cert.set_not_before(&Asn1Time::days_from_now(0).expect("time"))
+            .expect("not before");
+        cert.set_not_after(&Asn1Time::days_from_now(1).expect("time"))
+            .expect("not after");
+        cert.sign(&key, MessageDigest::sha256()).expect("sign");
+        let cert = cert.build();
+
+        let mut acceptor =
+            SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).expect("acceptor");
+        acceptor.set_private_key(&key).expect("key");
+        acceptor.set_certificate(&cert).expect("cert");
+        let acceptor = acceptor.build();
+
+        let listener = match "127.0.0.1:0".parse::<ListenAddr>().expect("listen addr") {
+            ListenAddr::Tcp(addr) => {
+                Listener::Tcp(bind_listener(addr, None, false).expect("bind"))
+            }
+            ListenAddr::Unix(_) => unreachable!("tcp address parsed as unix socket"),
+        };
+        let bound_addr = listener.bound_addr().expect("bound addr");
+
+        let server = tokio::spawn(async move {
+            let (stream, _addr) = listener.accept().await.expect("accept");
+            let ssl = Ssl::new(acceptor.context()).expect("ssl");
+            let mut stream = Box::pin(SslStream::new(ssl, stream).expect("ssl stream"));
+            stream.as_mut().accept().await.expect("server handshake");
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.expect("read");
+            assert_eq!(&buf, b"ping");
+        });
+
+        let mut connector = SslConnector::builder(SslMethod::tls()).expect("connector");
+        connector.set_verify(SslVerifyMode::NONE);
+        let connector = connector.build();
+        let tcp = tokio::net::TcpStream::connect(&bound_addr)
+            .await
+            .expect("connect");
+        let ssl = connector
+            .configure()
+            .expect("configure")
+            .into_ssl("localhost")
+            .expect("into ssl");
+        let mut stream = Box::pin(SslStream::new(ssl, tcp).expect("ssl stream"));
+        stream.as_mut().connect().await.expect("client handshake");
+        stream.write_all(b"ping").await.expect("write");
+        stream.flush().await.expect("flush");
+
+        server.await.expect("server task");
+    }
+
+    #[test]
+    fn test_listen_addr_parsing() {
+        assert!(matches!(
+            "127.0.0.1:8000".parse::<ListenAddr>(),
+            Ok(ListenAddr::Tcp(_))
+        ));
+        assert!(matches!(
+            "/var/run/mononoke.sock".parse::<ListenAddr>(),
+            Ok(ListenAddr::Unix(_))
+        ));
+        assert!("not-an-address".parse::<ListenAddr>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_listener_reuse_address() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().expect("addr");
+
+        let listener = bind_listener(addr, Some(64), true).expect("bind");
+        let bound_addr = listener.local_addr().expect("local addr");
+        drop(listener);
+
+        // With SO_REUSEADDR the same address can be rebound immediately.
+        let rebound = bind_listener(bound_addr, None, true).expect("rebind");
+        assert_eq!(rebound.local_addr().expect("local addr"), bound_addr);
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_in_flight_connections() {
+        let logger = Logger::root(slog::Discard, slog::o!());
+
+        let completed = Arc::new(AtomicBool::new(false));
+
+        // Simulate an in-flight connection the way spawn_task tracks them.
+        let conn_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+        OPEN_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        let (task, abort_handle) = futures::future::abortable({
+            let completed = completed.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                completed.store(true, Ordering::Relaxed);
+            }
+        });
+        CONNECTION_ABORT_HANDLES
+            .lock()
+            .expect("lock poisoned")
+            .insert(conn_id, abort_handle);
+        tokio::spawn(async move {
+            let _ = task.await;
+            CONNECTION_ABORT_HANDLES
+                .lock()
+                .expect("lock poisoned")
+                .remove(&conn_id);
+            OPEN_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+        });
+
+        // The in-flight request completes before drain returns.
+        drain_connections(&logger, Some(Duration::from_secs(10))).await;
+        assert!(completed.load(Ordering::Relaxed));
+        assert_eq!(OPEN_CONNECTIONS.load(Ordering::Relaxed), 0);
+    }
+}
@@ -8,12 +8,39 @@
 use mononoke_types::RepositoryId;
 use thiserror::Error;
 
+/// Why a repo refused to serve a request. Mapped from config state in
+/// `repo_handlers::repo_rejection_reason` so the client message and the
+/// server log agree on the precise reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoRejectionReason {
+    /// The repo is disabled in its config (`enabled = false`).
+    RepoDisabled,
+    /// The repo is read-only and the request needs write access.
+    ReadOnly,
+}
+
+impl std::fmt::Display for RepoRejectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RepoDisabled => write!(f, "repo is disabled"),
+            Self::ReadOnly => write!(f, "repo is read-only"),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ErrorKind {
     #[error("connection does not have a client certificate")]
     ConnectionNoClientCertificate,
     #[error("Unauthorized access, permission denied")]
     AuthorizationFailed,
+    #[error("Client identity {0} is denylisted, connection refused")]
+    ClientIdentityDenied(String),
     #[error("Large repo not found: {0}")]
     LargeRepoNotFound(RepositoryId),
+    #[error("Repo {repo} is not serving this request: {reason}")]
+    RepoRejected {
+        repo: String,
+        reason: RepoRejectionReason,
+    },
 }
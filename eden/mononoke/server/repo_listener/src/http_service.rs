@@ -16,11 +16,9 @@ use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
 use bookmarks::BookmarksRef;
-#[cfg(fbcode_build)]
 use clientinfo::ClientEntryPoint;
-#[cfg(fbcode_build)]
 use clientinfo::ClientInfo;
-#[cfg(fbcode_build)]
+use clientinfo::ClientRequestInfo;
 use clientinfo::CLIENT_INFO_HEADER;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
@@ -130,6 +128,10 @@ impl<S> Clone for MononokeHttpService<S> {
     }
 }
 
+fn readiness_body(ready: bool) -> &'static str {
+    if ready { "OK" } else { "NOT_READY" }
+}
+
 fn bump_qps(headers: &HeaderMap, qps: Option<&Qps>) -> Result<()> {
     let qps = match qps {
         Some(qps) => qps,
@@ -149,6 +151,19 @@ where
     S: MononokeStream,
 {
     async fn handle(&self, req: Request<Body>) -> Result<Response<Body>, HttpError> {
+        // Readiness probe for load balancers: reports whether startup has
+        // completed, unlike /health_check which reports liveness.
+        if req.method() == Method::GET && req.uri().path() == "/health" {
+            let res = readiness_body(self.acceptor().ready_service.is_ready());
+
+            let res = Response::builder()
+                .status(http::StatusCode::OK)
+                .body(res.into())
+                .map_err(HttpError::internal)?;
+
+            return Ok(res);
+        }
+
         if req.method() == Method::GET
             && (req.uri().path() == "/" || req.uri().path() == "/health_check")
         {
@@ -452,6 +467,20 @@ where
     }
 }
 
+/// Ensure wireproto metadata always carries a `ClientRequestInfo`: keep the
+/// client-provided one (and its correlator) when present, otherwise fall
+/// back to a generated correlator so the request is still traceable end to
+/// end in server logs.
+fn client_info_with_correlator(client_info: Option<ClientInfo>) -> ClientInfo {
+    let mut client_info = client_info.unwrap_or_else(|| {
+        ClientInfo::default_with_entry_point(ClientEntryPoint::SaplingRemoteApi)
+    });
+    if client_info.request_info.is_none() {
+        client_info.add_request_info(ClientRequestInfo::new(ClientEntryPoint::SaplingRemoteApi));
+    }
+    client_info
+}
+
 // See https://tools.ietf.org/html/rfc6455#section-1.3
 fn calculate_websocket_accept(headers: &HeaderMap<HeaderValue>) -> String {
     let mut sha1 = Sha1::new();
@@ -480,7 +509,12 @@ mod h2m {
 
         let _ = conn.pending.acceptor.common_config; // Fix compiler warning in OSS build
 
-        Ok(Metadata::new(
+        let client_info: Option<ClientInfo> = headers
+            .get(CLIENT_INFO_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|ci| serde_json::from_str(ci).ok());
+
+        let mut metadata = Metadata::new(
             Some(&generate_session_id().to_string()),
             (*conn.identities).clone(),
             debug,
@@ -493,7 +527,11 @@ mod h2m {
             Some(conn.pending.addr.ip()),
             Some(conn.pending.addr.port()),
         )
-        .await)
+        .await;
+
+        metadata.add_client_info(client_info_with_correlator(client_info));
+
+        Ok(metadata)
     }
 }
 
@@ -590,10 +628,7 @@ mod h2m {
                 )
                 .await;
 
-                let client_info = client_info.unwrap_or_else(|| {
-                    ClientInfo::default_with_entry_point(ClientEntryPoint::SaplingRemoteApi)
-                });
-                metadata.add_client_info(client_info);
+                metadata.add_client_info(client_info_with_correlator(client_info));
 
                 metadata_populate_trusted(&mut metadata, headers)?;
                 return Ok(metadata);
@@ -619,11 +654,26 @@ mod h2m {
         )
         .await;
 
-        let client_info = client_info.unwrap_or_else(|| {
-            ClientInfo::default_with_entry_point(ClientEntryPoint::SaplingRemoteApi)
-        });
-        metadata.add_client_info(client_info);
+        metadata.add_client_info(client_info_with_correlator(client_info));
 
         Ok(metadata)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mononoke_app::fb303::ReadyFlagService;
+
+    use super::*;
+
+    #[test]
+    fn test_readiness_probe() {
+        let service = ReadyFlagService::new();
+
+        // Before startup completes the probe reports NOT_READY.
+        assert_eq!(readiness_body(service.is_ready()), "NOT_READY");
+
+        service.set_ready();
+        assert_eq!(readiness_body(service.is_ready()), "OK");
+    }
+}
@@ -12,6 +12,7 @@
 mod connection_acceptor;
 mod errors;
 mod http_service;
+mod metrics;
 mod netspeedtest;
 mod repo_handlers;
 mod request_handler;
@@ -23,6 +24,7 @@ use std::sync::Arc;
 
 use anyhow::Context as _;
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use cached_config::ConfigStore;
 use fbinit::FacebookInit;
 use futures::channel::oneshot;
@@ -40,6 +42,12 @@ use slog::Logger;
 
 use crate::connection_acceptor::connection_acceptor;
 pub use crate::connection_acceptor::wait_for_connections_closed;
+pub use crate::connection_acceptor::ClientIdentityDenylist;
+pub use crate::connection_acceptor::ListenAddr;
+pub use crate::metrics::MetricsSink;
+pub use crate::metrics::NullMetricsSink;
+pub use crate::metrics::RequestMetric;
+pub use crate::repo_handlers::RepoFilter;
 
 const CONFIGERATOR_RATE_LIMITING_CONFIG: &str = "scm/mononoke/ratelimiting/ratelimits";
 
@@ -49,8 +57,8 @@ pub async fn create_repo_listeners<'a>(
     common_config: CommonConfig,
     mononoke: Arc<Mononoke>,
     root_log: Logger,
-    sockname: String,
-    tls_acceptor: SslAcceptor,
+    listen_addr: ListenAddr,
+    tls_acceptor: Arc<ArcSwap<SslAcceptor>>,
     service: ReadyFlagService,
     terminate_process: oneshot::Receiver<()>,
     config_store: &'a ConfigStore,
@@ -62,6 +70,9 @@ pub async fn create_repo_listeners<'a>(
     acl_provider: &dyn AclProvider,
     readonly: bool,
     mtls_disabled: bool,
+    metrics_sink: Arc<dyn MetricsSink>,
+    identity_denylist: ClientIdentityDenylist,
+    repo_filter: Option<RepoFilter>,
 ) -> Result<()> {
     let rate_limiter = {
         let handle = config_store
@@ -101,7 +112,7 @@ pub async fn create_repo_listeners<'a>(
         fb,
         configs,
         common_config,
-        sockname,
+        listen_addr,
         service,
         root_log,
         mononoke,
@@ -122,6 +133,9 @@ pub async fn create_repo_listeners<'a>(
         acl_provider,
         readonly,
         mtls_disabled,
+        metrics_sink,
+        identity_denylist,
+        repo_filter,
     )
     .await
 }
@@ -5,18 +5,26 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use anyhow::anyhow;
 use metaconfig_types::RepoClientKnobs;
+use metaconfig_types::RepoConfig;
 use mononoke_api::Mononoke;
 use mononoke_api::Repo;
 use repo_client::PushRedirectorArgs;
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::Logger;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
 use wireproto_handler::BackupSourceRepo;
 
+use metaconfig_types::RepoReadOnly;
+
 use crate::errors::ErrorKind;
+use crate::errors::RepoRejectionReason;
 
 #[derive(Clone)]
 pub struct RepoHandler {
@@ -28,13 +36,110 @@ pub struct RepoHandler {
     pub maybe_backup_repo_source: Option<BackupSourceRepo>,
 }
 
-pub fn repo_handler(mononoke: Arc<Mononoke>, repo_name: &str) -> anyhow::Result<RepoHandler> {
+/// Predicate selecting which repos this instance serves. Repos that do not
+/// match are treated as if they were not configured on this server, so their
+/// handlers (push redirectors, backup sources) are never built. Useful for
+/// shards and tests that serve a subset of the configured repos.
+pub type RepoFilter = Arc<dyn Fn(&str, &RepoConfig) -> bool + Send + Sync>;
+
+/// Whether `repo_name` is served, honoring an optional [`RepoFilter`].
+/// `None` serves everything.
+pub(crate) fn is_repo_served(
+    repo_filter: Option<&RepoFilter>,
+    repo_name: &str,
+    config: &RepoConfig,
+) -> bool {
+    repo_filter.map_or(true, |filter| filter(repo_name, config))
+}
+
+/// Hands out per-repo permits sized from `max_concurrent_requests` in the
+/// repo config, so one busy repo cannot starve others sharing the process.
+/// Repos without the setting stay unbounded. Shared across connections via
+/// the acceptor.
+pub struct RepoConcurrencyLimiter {
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl RepoConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self {
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for a request slot for `repo_name`. Returns `None` immediately
+    /// if the repo has no configured limit; otherwise the returned permit
+    /// holds the slot until dropped.
+    pub async fn acquire(
+        &self,
+        repo_name: &str,
+        config: &RepoConfig,
+    ) -> Option<OwnedSemaphorePermit> {
+        let limit = match config.max_concurrent_requests {
+            Some(limit) if limit > 0 => limit,
+            _ => return None,
+        };
+        let semaphore = self
+            .semaphores
+            .lock()
+            .expect("poisoned lock")
+            .entry(repo_name.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+            .clone();
+        Some(
+            semaphore
+                .acquire_owned()
+                .await
+                .expect("repo concurrency semaphore closed"),
+        )
+    }
+}
+
+/// Maps config state to the reason a repo cannot serve a request, if any.
+/// `for_write` should be set for requests that need write access; read-only
+/// repos still serve reads. This is the single place deciding rejections so
+/// client errors and server logs report the same reason.
+pub(crate) fn repo_rejection_reason(
+    config: &RepoConfig,
+    for_write: bool,
+) -> Option<RepoRejectionReason> {
+    if !config.enabled {
+        return Some(RepoRejectionReason::RepoDisabled);
+    }
+    if for_write {
+        if let RepoReadOnly::ReadOnly(_) = config.readonly {
+            return Some(RepoRejectionReason::ReadOnly);
+        }
+    }
+    None
+}
+
+pub fn repo_handler(
+    mononoke: Arc<Mononoke>,
+    repo_name: &str,
+    repo_filter: Option<&RepoFilter>,
+) -> anyhow::Result<RepoHandler> {
     let source_repo = mononoke.raw_repo(repo_name).ok_or_else(|| {
         anyhow!(
             "Requested repo {} is not being served by this server",
             &repo_name
         )
     })?;
+    if !is_repo_served(repo_filter, repo_name, source_repo.config()) {
+        return Err(anyhow!(
+            "Requested repo {} is not being served by this instance (filtered out)",
+            &repo_name
+        ));
+    }
+    // Opening a connection only needs read access; writes are rejected later
+    // by the wireproto layer with the read-only message from the config.
+    if let Some(reason) = repo_rejection_reason(source_repo.config(), false) {
+        return Err(ErrorKind::RepoRejected {
+            repo: repo_name.to_string(),
+            reason,
+        }
+        .into());
+    }
     let base = source_repo.repo_handler_base.clone();
     let maybe_push_redirector_args = match &base.maybe_push_redirector_base {
         Some(push_redirector_base) => {
@@ -84,3 +189,82 @@ pub fn repo_handler(mononoke: Arc<Mononoke>, repo_name: &str) -> anyhow::Result<
         maybe_backup_repo_source,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_filter_selects_repos() {
+        let config = RepoConfig::default();
+        let filter: RepoFilter = Arc::new(|name, _config| name.starts_with("prod"));
+        assert!(is_repo_served(Some(&filter), "prod-repo", &config));
+        assert!(!is_repo_served(Some(&filter), "test-repo", &config));
+        // No filter serves everything.
+        assert!(is_repo_served(None, "test-repo", &config));
+    }
+
+    #[test]
+    fn test_repo_rejection_reason() {
+        // An enabled read-write repo serves everything.
+        let mut config = RepoConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        assert_eq!(repo_rejection_reason(&config, false), None);
+        assert_eq!(repo_rejection_reason(&config, true), None);
+
+        // A disabled repo rejects reads and writes alike, and the reason
+        // renders into an actionable client message.
+        config.enabled = false;
+        let reason = repo_rejection_reason(&config, false).unwrap();
+        assert_eq!(reason, RepoRejectionReason::RepoDisabled);
+        let err = ErrorKind::RepoRejected {
+            repo: "test-repo".to_string(),
+            reason,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Repo test-repo is not serving this request: repo is disabled"
+        );
+
+        // A read-only repo only rejects writes.
+        config.enabled = true;
+        config.readonly = RepoReadOnly::ReadOnly("maintenance".to_string());
+        assert_eq!(repo_rejection_reason(&config, false), None);
+        assert_eq!(
+            repo_rejection_reason(&config, true),
+            Some(RepoRejectionReason::ReadOnly)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_per_repo_concurrency_gate() {
+        let limiter = RepoConcurrencyLimiter::new();
+        let limited = RepoConfig {
+            enabled: true,
+            max_concurrent_requests: Some(1),
+            ..Default::default()
+        };
+        let unlimited = RepoConfig {
+            enabled: true,
+            ..Default::default()
+        };
+
+        // The first request for the limited repo gets a permit.
+        let first = limiter.acquire("busy", &limited).await;
+        assert!(first.is_some());
+
+        // The second request for the same repo waits for the slot...
+        let second = limiter.acquire("busy", &limited);
+        futures::pin_mut!(second);
+        assert!(futures::poll!(second.as_mut()).is_pending());
+
+        // ...while a repo without a limit is unaffected.
+        assert!(limiter.acquire("other", &unlimited).await.is_none());
+
+        // Releasing the permit unblocks the waiter.
+        drop(first);
+        assert!(second.await.is_some());
+    }
+}
@@ -5,14 +5,18 @@
  * GNU General Public License version 2.
  */
 
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Instant;
 
 use anyhow::anyhow;
-use anyhow::Context;
 use anyhow::Error;
 use anyhow::Result;
 use bytes::Bytes;
+use clientinfo::ClientEntryPoint;
+use clientinfo::ClientRequestInfo;
 use connection_security_checker::ConnectionSecurityChecker;
 use context::LoggingContainer;
 use context::SessionContainer;
@@ -29,6 +33,7 @@ use hgproto::sshproto;
 use hgproto::HgProtoHandler;
 use maplit::hashmap;
 use maplit::hashset;
+use metadata::Metadata;
 use mononoke_api::Mononoke;
 use mononoke_configs::MononokeConfigs;
 use qps::Qps;
@@ -50,7 +55,11 @@ use stats::prelude::*;
 use time_ext::DurationExt;
 
 use crate::errors::ErrorKind;
+use crate::metrics::MetricsSink;
+use crate::metrics::RequestMetric;
 use crate::repo_handlers::repo_handler;
+use crate::repo_handlers::RepoConcurrencyLimiter;
+use crate::repo_handlers::RepoFilter;
 use crate::repo_handlers::RepoHandler;
 
 define_stats! {
@@ -73,7 +82,12 @@ pub async fn request_handler(
     scribe: Scribe,
     qps: Option<Arc<Qps>>,
     readonly: bool,
+    metrics_sink: Arc<dyn MetricsSink>,
+    repo_filter: Option<RepoFilter>,
+    repo_concurrency: Arc<RepoConcurrencyLimiter>,
 ) -> Result<()> {
+    let start = Instant::now();
+
     let Stdio {
         stdin,
         stdout,
@@ -83,18 +97,41 @@ pub async fn request_handler(
 
     let session_id = metadata.session_id();
 
+    // Correlator tracing this request end to end across client and server
+    // logs: the one the client sent in its handshake when present, a
+    // generated one otherwise.
+    let client_correlator = wireproto_correlator(&metadata);
+
     // We don't have a repository yet, so create without server drain
-    let conn_log = create_conn_logger(stderr.clone(), None, Some(session_id));
+    let conn_log = create_conn_logger(stderr.clone(), None, Some(session_id))
+        .new(o!("client_correlator" => client_correlator.clone()));
 
-    let handler = repo_handler(mononoke, &reponame).with_context(|| {
-        error!(
-            conn_log,
-            "Requested repo \"{}\" does not exist or is disabled", &reponame;
-            "remote" => "true"
-        );
+    let handler = match repo_handler(mononoke, &reponame, repo_filter.as_ref()) {
+        Ok(handler) => handler,
+        Err(err) => {
+            // Typed rejections carry the precise reason; surface it to the
+            // client and the server log instead of the generic message.
+            if let Some(ErrorKind::RepoRejected { reason, .. }) = err.downcast_ref::<ErrorKind>() {
+                error!(
+                    conn_log,
+                    "Requested repo \"{}\" is not serving this request: {}", &reponame, reason;
+                    "remote" => "true"
+                );
+
+                metrics_sink.record(RequestMetric::early_failure(&reponame, start.elapsed()));
+                return Err(err);
+            }
 
-        anyhow!("Unknown Repo: {}", &reponame)
-    })?;
+            error!(
+                conn_log,
+                "Requested repo \"{}\" does not exist or is disabled", &reponame;
+                "remote" => "true"
+            );
+
+            metrics_sink.record(RequestMetric::early_failure(&reponame, start.elapsed()));
+            return Err(err.context(anyhow!("Unknown Repo: {}", &reponame)));
+        }
+    };
 
     let RepoHandler {
         logger,
@@ -106,15 +143,23 @@ pub async fn request_handler(
     } = handler;
 
     // Upgrade log to include server drain
-    let conn_log = create_conn_logger(stderr.clone(), Some(logger), Some(session_id));
+    let conn_log = create_conn_logger(stderr.clone(), Some(logger), Some(session_id))
+        .new(o!("client_correlator" => client_correlator.clone()));
+
+    // Wait for a per-repo request slot, if the repo caps concurrency. The
+    // permit is held for the rest of the request.
+    let _repo_permit = repo_concurrency.acquire(&reponame, repo.config()).await;
 
     scuba = scuba.with_seq("seq");
-    scuba.add("repo", reponame);
+    scuba.add("repo", reponame.clone());
     if let Some(config_info) = configs.config_info().as_ref() {
         scuba.add("config_store_version", config_info.content_hash.clone());
         scuba.add("config_store_last_updated_at", config_info.last_updated_at);
     }
     scuba.add_metadata(&metadata);
+    // Present even when the client sent no ClientRequestInfo, in which case
+    // the generated fallback is logged.
+    scuba.add("client_correlator", client_correlator.clone());
     scuba.sample_for_identities(metadata.identities());
 
     let rate_limiter = rate_limiter.map(|r| r.get_rate_limiter());
@@ -123,6 +168,7 @@ pub async fn request_handler(
             scuba.log_with_msg("Request rejected due to load shedding", format!("{}", err));
             error!(conn_log, "Request rejected due to load shedding: {}", err; "remote" => "true");
 
+            metrics_sink.record(RequestMetric::early_failure(&reponame, start.elapsed()));
             return Err(err.into());
         }
     }
@@ -137,6 +183,7 @@ pub async fn request_handler(
         scuba.log_with_msg("Authorization failed", format!("{}", err));
         error!(conn_log, "Authorization failed: {}", err; "remote" => "true");
 
+        metrics_sink.record(RequestMetric::early_failure(&reponame, start.elapsed()));
         return Err(err);
     }
 
@@ -165,6 +212,17 @@ pub async fn request_handler(
     );
     let request_perf_counters = repo_client.request_perf_counters();
 
+    // Count the bytes flowing in either direction for the request metric
+    let bytes_in = Arc::new(AtomicU64::new(0));
+    let bytes_out = Arc::new(AtomicU64::new(0));
+
+    let stdin = {
+        let bytes_in = bytes_in.clone();
+        stdin.inspect_ok(move |bytes| {
+            bytes_in.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        })
+    };
+
     // Construct a hg protocol handler
     let proto_handler = HgProtoHandler::new(
         conn_log.clone(),
@@ -180,7 +238,13 @@ pub async fn request_handler(
     // send responses back
     let endres = proto_handler
         .into_stream()
-        .inspect_ok(move |bytes| session.bump_load(Metric::EgressBytes, bytes.len() as f64))
+        .inspect_ok({
+            let bytes_out = bytes_out.clone();
+            move |bytes| {
+                session.bump_load(Metric::EgressBytes, bytes.len() as f64);
+                bytes_out.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            }
+        })
         .map_err(Error::from)
         .map_ok(|b| Bytes::copy_from_slice(b.as_ref()))
         .forward(stdout.sink_map_err(Error::from))
@@ -198,6 +262,7 @@ pub async fn request_handler(
 
     let mut scuba = scuba.clone();
 
+    let wireproto_command = wireproto_calls.join(",");
     scuba
         .add_future_stats(&stats)
         .add("wireproto_commands", wireproto_calls);
@@ -227,6 +292,16 @@ pub async fn request_handler(
         }
     }
 
+    // Exactly one metric per completed request, success or failure.
+    metrics_sink.record(RequestMetric {
+        repo: reponame,
+        command: wireproto_command,
+        duration: stats.completion_time,
+        bytes_in: bytes_in.load(Ordering::Relaxed),
+        bytes_out: bytes_out.load(Ordering::Relaxed),
+        success: result.is_ok(),
+    });
+
     if let Err(err) = result {
         error!(&conn_log, "Command failed";
             SlogKVError(err),
@@ -237,6 +312,16 @@ pub async fn request_handler(
     Ok(())
 }
 
+/// Correlator used to trace a wireproto request end to end: the one the
+/// client sent in its handshake `ClientInfo` when present, otherwise a
+/// freshly generated one.
+pub(crate) fn wireproto_correlator(metadata: &Metadata) -> String {
+    match metadata.client_request_info() {
+        Some(client_info) => client_info.correlator.clone(),
+        None => ClientRequestInfo::new(ClientEntryPoint::Sapling).correlator,
+    }
+}
+
 pub fn create_conn_logger(
     stderr: mpsc::UnboundedSender<Bytes>,
     server_logger: Option<Logger>,
@@ -273,3 +358,38 @@ pub fn create_conn_logger(
         Logger::root(client_drain.ignore_res(), decorator)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clientinfo::ClientInfo;
+    use permission_checker::MononokeIdentitySet;
+
+    use super::*;
+
+    async fn test_metadata() -> Metadata {
+        Metadata::new(None, MononokeIdentitySet::new(), false, true, None, None).await
+    }
+
+    #[tokio::test]
+    async fn test_wireproto_correlator_prefers_client_value() {
+        let mut metadata = test_metadata().await;
+        let mut client_info = ClientInfo::default();
+        client_info.add_request_info(ClientRequestInfo::new_ext(
+            ClientEntryPoint::Sapling,
+            "known-correlator".to_string(),
+        ));
+        metadata.add_client_info(client_info);
+
+        // The per-request context carries the client-provided correlator,
+        // which is what ends up on the request's log and scuba lines.
+        assert_eq!(wireproto_correlator(&metadata), "known-correlator");
+    }
+
+    #[tokio::test]
+    async fn test_wireproto_correlator_generates_fallback() {
+        // Without a client-provided ClientRequestInfo a correlator is still
+        // generated, so the request remains traceable.
+        let metadata = test_metadata().await;
+        assert!(!wireproto_correlator(&metadata).is_empty());
+    }
+}
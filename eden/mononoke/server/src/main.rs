@@ -14,6 +14,7 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
 use cache_warmup::cache_warmup;
 use cache_warmup::CacheWarmupKind;
@@ -68,7 +69,8 @@ struct MononokeServerArgs {
     shutdown_timeout_args: ShutdownTimeoutArgs,
     #[clap(flatten)]
     scribe_logging_args: ScribeLoggingArgs,
-    /// TCP address to listen to in format `host:port
+    /// TCP address to listen to in format `host:port`, or an absolute
+    /// path to listen on a unix domain socket
     #[clap(long)]
     listening_host_port: String,
     /// Path for file in which to write the bound tcp address in rust std::net::SocketAddr format
@@ -287,13 +289,13 @@ fn main(fb: FacebookInit) -> Result<()> {
             builder.set_verify(openssl::ssl::SslVerifyMode::NONE)
         }
 
-        builder.build()
+        Arc::new(ArcSwap::from_pointee(builder.build()))
     };
 
     info!(root_log, "Creating repo listeners");
 
     let scribe = args.scribe_logging_args.get_scribe(fb)?;
-    let host_port = args.listening_host_port;
+    let listen_addr: repo_listener::ListenAddr = args.listening_host_port.parse()?;
     let bound_addr_file = args.bound_address_file;
 
     let service = ReadyFlagService::new();
@@ -373,7 +375,7 @@ fn main(fb: FacebookInit) -> Result<()> {
                 common,
                 mononoke.clone(),
                 root_log,
-                host_port,
+                listen_addr,
                 acceptor,
                 service,
                 terminate_receiver,
@@ -386,6 +388,10 @@ fn main(fb: FacebookInit) -> Result<()> {
                 env.acl_provider.as_ref(),
                 args.readonly.readonly,
                 args.tls_args.disable_mtls,
+                Arc::new(repo_listener::NullMetricsSink),
+                repo_listener::ClientIdentityDenylist::default(),
+                // The main server serves every configured repo.
+                None,
             )
             .await
         }
@@ -5,17 +5,165 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::HashMap;
+
+use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
+use clap::ValueEnum;
+use metaconfig_types::BlobConfig;
+use metaconfig_types::RepoConfig;
+use metaconfig_types::RepoReadOnly;
 use mononoke_app::MononokeApp;
 use regex::Regex;
 
+/// Label of the config source `MononokeApp` actively serves from.
+const ACTIVE_SOURCE: &str = "active";
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Sort {
+    /// Sort by repo id.
+    Id,
+    /// Sort by repo name (case-insensitive).
+    Name,
+}
+
 /// List known repositories
 #[derive(Parser)]
 pub struct CommandArgs {
     /// Pattern to match against repo names.
     pattern: Option<String>,
+
+    /// Field to sort the listing by.
+    #[clap(long, value_enum, default_value_t = Sort::Id)]
+    sort: Sort,
+
+    /// Only list repos from the named config source. The source the app
+    /// actively serves from is named "active".
+    #[clap(long)]
+    source: Option<String>,
+
+    /// Print the config source of each repo as an extra column.
+    #[clap(long)]
+    show_source: bool,
+
+    /// Only list repos whose config matches `key=value`. May be repeated;
+    /// clauses AND together. Supported keys: storage (blobstore kind, ex.
+    /// "files"), readonly (true/false), hooks (true/false: whether any
+    /// hooks are configured).
+    #[clap(long = "where", value_name = "KEY=VALUE")]
+    r#where: Vec<String>,
+}
+
+/// Keys accepted by `--where`, for error messages.
+const SUPPORTED_WHERE_KEYS: &str = "storage, readonly, hooks";
+
+/// Attribute filter parsed from a `--where key=value` flag.
+#[derive(Debug, Clone)]
+enum WhereClause {
+    /// Blobstore kind backing the repo's storage.
+    Storage(String),
+    /// Whether the repo rejects pushes.
+    Readonly(bool),
+    /// Whether any hooks are configured.
+    Hooks(bool),
+}
+
+fn parse_where_bool(key: &str, value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => bail!(
+            "invalid value '{}' for --where key '{}', expected true or false",
+            value,
+            key
+        ),
+    }
+}
+
+fn parse_where_clause(clause: &str) -> Result<WhereClause> {
+    let (key, value) = clause
+        .split_once('=')
+        .with_context(|| format!("invalid --where clause '{}', expected key=value", clause))?;
+    match key {
+        "storage" => Ok(WhereClause::Storage(value.to_string())),
+        "readonly" => Ok(WhereClause::Readonly(parse_where_bool(key, value)?)),
+        "hooks" => Ok(WhereClause::Hooks(parse_where_bool(key, value)?)),
+        _ => bail!(
+            "unknown --where key '{}', supported keys: {}",
+            key,
+            SUPPORTED_WHERE_KEYS
+        ),
+    }
+}
+
+/// Short name of the blobstore kind backing the repo's storage. Wrapper
+/// blobstores (logging, pack) report the wrapped kind.
+fn storage_kind(blobstore: &BlobConfig) -> &'static str {
+    match blobstore {
+        BlobConfig::Disabled => "disabled",
+        BlobConfig::Files { .. } => "files",
+        BlobConfig::Sqlite { .. } => "sqlite",
+        BlobConfig::Manifold { .. } | BlobConfig::ManifoldWithTtl { .. } => "manifold",
+        BlobConfig::Mysql { .. } => "mysql",
+        BlobConfig::MultiplexedWal { .. } => "multiplexed-wal",
+        BlobConfig::Logging { blobconfig, .. } => storage_kind(blobconfig),
+        BlobConfig::Pack { blobconfig, .. } => storage_kind(blobconfig),
+        BlobConfig::S3 { .. } => "s3",
+        BlobConfig::AwsS3 { .. } => "aws-s3",
+    }
+}
+
+fn matches_clause(repo_config: &RepoConfig, clause: &WhereClause) -> bool {
+    match clause {
+        WhereClause::Storage(kind) => storage_kind(&repo_config.storage_config.blobstore) == kind,
+        WhereClause::Readonly(readonly) => {
+            matches!(repo_config.readonly, RepoReadOnly::ReadOnly(_)) == *readonly
+        }
+        WhereClause::Hooks(hooks) => !repo_config.hooks.is_empty() == *hooks,
+    }
+}
+
+/// Flatten labeled config sources into `(name, config, source)` entries,
+/// optionally restricted to a single source.
+fn collect_repos<'a>(
+    sources: &[(&'a str, &'a HashMap<String, RepoConfig>)],
+    only_source: Option<&str>,
+) -> Result<Vec<(&'a String, &'a RepoConfig, &'a str)>> {
+    if let Some(only_source) = only_source {
+        if !sources.iter().any(|(label, _)| *label == only_source) {
+            bail!(
+                "unknown config source '{}', known sources: {}",
+                only_source,
+                sources
+                    .iter()
+                    .map(|(label, _)| *label)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+    Ok(sources
+        .iter()
+        .filter(|(label, _)| only_source.map_or(true, |only| only == *label))
+        .flat_map(|(label, repos)| {
+            repos
+                .iter()
+                .map(move |(repo_name, repo_config)| (repo_name, repo_config, *label))
+        })
+        .collect())
+}
+
+fn sort_repos(repos: &mut [(&String, &RepoConfig, &str)], sort: Sort) {
+    match sort {
+        Sort::Id => {
+            repos.sort_unstable_by_key(|(_repo_name, repo_config, _source)| repo_config.repoid)
+        }
+        Sort::Name => repos.sort_unstable_by_key(|(repo_name, repo_config, _source)| {
+            (repo_name.to_lowercase(), repo_config.repoid)
+        }),
+    }
 }
 
 pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
@@ -26,18 +174,146 @@ pub async fn run(app: MononokeApp, args: CommandArgs) -> Result<()> {
         .transpose()
         .context("Failed to parse pattern")?;
 
+    let clauses = args
+        .r#where
+        .iter()
+        .map(|clause| parse_where_clause(clause))
+        .collect::<Result<Vec<_>>>()?;
+
     let configs = app.repo_configs();
-    let mut repos = configs.repos.iter().collect::<Vec<_>>();
-    repos.sort_unstable_by_key(|(_repo_name, repo_config)| repo_config.repoid);
+    // MononokeApp currently exposes a single config source. The listing is
+    // structured around labeled sources so setups serving several loaded
+    // config bundles can extend this list.
+    let sources: Vec<(&str, &HashMap<String, RepoConfig>)> =
+        vec![(ACTIVE_SOURCE, &configs.repos)];
+    let mut repos = collect_repos(&sources, args.source.as_deref())?;
+    sort_repos(&mut repos, args.sort);
 
-    for (repo_name, repo_config) in repos.into_iter() {
+    for (repo_name, repo_config, source) in repos.into_iter() {
         if let Some(pattern) = &pattern {
             if !pattern.is_match(repo_name) {
                 continue;
             }
         }
-        println!("{} {}", repo_config.repoid, repo_name);
+        if !clauses
+            .iter()
+            .all(|clause| matches_clause(repo_config, clause))
+        {
+            continue;
+        }
+        if args.show_source {
+            println!("{} {} {}", repo_config.repoid, repo_name, source);
+        } else {
+            println!("{} {}", repo_config.repoid, repo_name);
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use mononoke_types::RepositoryId;
+
+    use super::*;
+
+    fn repo_config(repoid: i32) -> RepoConfig {
+        RepoConfig {
+            repoid: RepositoryId::new(repoid),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sort_by_name_is_alphabetical() {
+        let names = ["zebra".to_string(), "Apple".to_string(), "mango".to_string()];
+        let configs = [repo_config(1), repo_config(3), repo_config(2)];
+        let mut repos: Vec<(&String, &RepoConfig, &str)> = names
+            .iter()
+            .zip(configs.iter())
+            .map(|(name, config)| (name, config, ACTIVE_SOURCE))
+            .collect();
+
+        sort_repos(&mut repos, Sort::Name);
+        let sorted_names: Vec<_> = repos.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(sorted_names, vec!["Apple", "mango", "zebra"]);
+
+        sort_repos(&mut repos, Sort::Id);
+        let sorted_ids: Vec<_> = repos
+            .iter()
+            .map(|(_, config, _)| config.repoid.id())
+            .collect();
+        assert_eq!(sorted_ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_where_filters() {
+        let mut files = repo_config(1);
+        files.storage_config.blobstore = BlobConfig::Files {
+            path: "/tmp/blobs".into(),
+        };
+        let mut sqlite = repo_config(2);
+        sqlite.storage_config.blobstore = BlobConfig::Sqlite {
+            path: "/tmp/db".into(),
+        };
+        sqlite.readonly = RepoReadOnly::ReadOnly("frozen".to_string());
+
+        // Filtering by storage kind.
+        let storage = parse_where_clause("storage=files").unwrap();
+        assert!(matches_clause(&files, &storage));
+        assert!(!matches_clause(&sqlite, &storage));
+
+        // Clauses AND together: files is read-write, so it fails the pair.
+        let readonly = parse_where_clause("readonly=true").unwrap();
+        assert!(matches_clause(&sqlite, &readonly));
+        assert!(
+            ![&storage, &readonly]
+                .iter()
+                .all(|clause| matches_clause(&files, clause))
+        );
+
+        // Wrapper blobstores report the wrapped kind.
+        let mut packed = repo_config(3);
+        packed.storage_config.blobstore = BlobConfig::Pack {
+            blobconfig: Box::new(BlobConfig::Files {
+                path: "/tmp/blobs".into(),
+            }),
+            pack_config: None,
+        };
+        assert!(matches_clause(&packed, &storage));
+
+        // Unknown keys are rejected with the supported ones listed.
+        let err = parse_where_clause("flavor=spicy").unwrap_err();
+        assert!(err.to_string().contains(SUPPORTED_WHERE_KEYS));
+        // Malformed clauses and bad boolean values are rejected too.
+        assert!(parse_where_clause("storage").is_err());
+        assert!(parse_where_clause("readonly=maybe").is_err());
+    }
+
+    #[test]
+    fn test_collect_repos_labels_sources() {
+        let active: HashMap<String, RepoConfig> =
+            [("repo-a".to_string(), repo_config(1))].into_iter().collect();
+        let backup: HashMap<String, RepoConfig> =
+            [("repo-b".to_string(), repo_config(2))].into_iter().collect();
+        let sources = vec![("active", &active), ("backup", &backup)];
+
+        // Repos from both sources appear, each labeled with its source.
+        let mut repos = collect_repos(&sources, None).unwrap();
+        sort_repos(&mut repos, Sort::Id);
+        let listed: Vec<_> = repos
+            .iter()
+            .map(|(name, _, source)| (name.as_str(), *source))
+            .collect();
+        assert_eq!(listed, vec![("repo-a", "active"), ("repo-b", "backup")]);
+
+        // Restricting to one source only lists its repos.
+        let repos = collect_repos(&sources, Some("backup")).unwrap();
+        assert_eq!(repos.len(), 1);
+        assert_eq!(repos[0].0, "repo-b");
+
+        // Unknown sources are rejected with the known ones listed.
+        let err = collect_repos(&sources, Some("prod")).unwrap_err();
+        assert!(err.to_string().contains("active, backup"));
+    }
+}
@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::env::var;
+
+use hostname::get_hostname;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Overrides the detected environment, mainly for testing.
+pub const ENV_SAPLING_CLIENT_ENVIRONMENT: &str = "SAPLING_CLIENT_ENVIRONMENT";
+
+/// Coarse classification of the host a client runs on, used by fleet
+/// analysis. Detection is best-effort and should never fail; hosts that
+/// match no rule are reported as `Unknown`.
+#[derive(Default, Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientEnvironment {
+    Sandcastle,
+    Devserver,
+    OnDemand,
+    Laptop,
+    #[default]
+    Unknown,
+}
+
+impl ClientEnvironment {
+    /// Detect the environment of the current host.
+    ///
+    /// The `SAPLING_CLIENT_ENVIRONMENT` environment variable overrides
+    /// detection; otherwise well-known environment variables and hostname
+    /// patterns are consulted.
+    pub fn detect() -> Self {
+        if let Ok(value) = var(ENV_SAPLING_CLIENT_ENVIRONMENT) {
+            if let Ok(environment) = ClientEnvironment::try_from(value.as_ref()) {
+                return environment;
+            }
+        }
+        if var("SANDCASTLE").is_ok() {
+            return ClientEnvironment::Sandcastle;
+        }
+        match get_hostname() {
+            Ok(hostname) => Self::from_hostname(&hostname),
+            Err(_) => ClientEnvironment::Unknown,
+        }
+    }
+
+    fn from_hostname(hostname: &str) -> Self {
+        let hostname = hostname.to_lowercase();
+        if hostname.starts_with("sandcastle") {
+            ClientEnvironment::Sandcastle
+        } else if hostname.starts_with("dev") || hostname.contains("devvm") {
+            ClientEnvironment::Devserver
+        } else if hostname.starts_with("od") {
+            ClientEnvironment::OnDemand
+        } else if hostname.ends_with(".local") || hostname.contains("mbp") {
+            ClientEnvironment::Laptop
+        } else {
+            ClientEnvironment::Unknown
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClientEnvironment::Sandcastle => "sandcastle",
+            ClientEnvironment::Devserver => "devserver",
+            ClientEnvironment::OnDemand => "ondemand",
+            ClientEnvironment::Laptop => "laptop",
+            ClientEnvironment::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for ClientEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl TryFrom<&str> for ClientEnvironment {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "sandcastle" => Ok(ClientEnvironment::Sandcastle),
+            "devserver" => Ok(ClientEnvironment::Devserver),
+            "ondemand" => Ok(ClientEnvironment::OnDemand),
+            "laptop" => Ok(ClientEnvironment::Laptop),
+            "unknown" => Ok(ClientEnvironment::Unknown),
+            _ => Err(anyhow::anyhow!("Invalid client environment: {}", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::remove_var;
+    use std::env::set_var;
+
+    use super::*;
+
+    #[test]
+    fn test_environment_override() {
+        set_var(ENV_SAPLING_CLIENT_ENVIRONMENT, "sandcastle");
+        assert_eq!(ClientEnvironment::detect(), ClientEnvironment::Sandcastle);
+        set_var(ENV_SAPLING_CLIENT_ENVIRONMENT, "LAPTOP");
+        assert_eq!(ClientEnvironment::detect(), ClientEnvironment::Laptop);
+        remove_var(ENV_SAPLING_CLIENT_ENVIRONMENT);
+    }
+
+    #[test]
+    fn test_environment_from_hostname() {
+        assert_eq!(
+            ClientEnvironment::from_hostname("sandcastle1234.example.com"),
+            ClientEnvironment::Sandcastle
+        );
+        assert_eq!(
+            ClientEnvironment::from_hostname("devvm5678.example.com"),
+            ClientEnvironment::Devserver
+        );
+        assert_eq!(
+            ClientEnvironment::from_hostname("my-mbp.local"),
+            ClientEnvironment::Laptop
+        );
+        assert_eq!(
+            ClientEnvironment::from_hostname("some-prod-host"),
+            ClientEnvironment::Unknown
+        );
+    }
+}
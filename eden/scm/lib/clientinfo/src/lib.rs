@@ -5,8 +5,13 @@
  * GNU General Public License version 2.
  */
 
+mod environment;
 mod request_info;
 
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use hostname::get_hostname;
@@ -24,6 +29,8 @@ use facebook::FbClientInfo;
 #[cfg(not(fbcode_build))]
 use oss as facebook;
 
+pub use crate::environment::ClientEnvironment;
+pub use crate::environment::ENV_SAPLING_CLIENT_ENVIRONMENT;
 pub use crate::request_info::get_client_request_info;
 pub use crate::request_info::get_client_request_info_thread_local;
 pub use crate::request_info::set_client_request_info_thread_local;
@@ -32,7 +39,7 @@ pub use crate::request_info::ClientRequestInfo;
 pub use crate::request_info::ENV_SAPLING_CLIENT_CORRELATOR;
 pub use crate::request_info::ENV_SAPLING_CLIENT_ENTRY_POINT;
 
-#[derive(Default, Clone, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[derive(Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct ClientInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hostname: Option<String>,
@@ -40,6 +47,15 @@ pub struct ClientInfo {
     pub fb: FbClientInfo,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_info: Option<ClientRequestInfo>,
+    /// Coarse environment tag of the host (e.g. sandcastle vs devserver),
+    /// detected from hostname patterns and well-known env vars.
+    #[serde(default)]
+    pub environment: ClientEnvironment,
+    /// Arbitrary caller-provided key/value pairs (e.g. CI metadata),
+    /// serialized under an `extra` object. See
+    /// [`ClientInfo::with_extra_fields`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, String>,
 }
 
 impl ClientInfo {
@@ -54,6 +70,8 @@ impl ClientInfo {
             hostname,
             fb,
             request_info: Some(cri),
+            environment: ClientEnvironment::detect(),
+            extra: BTreeMap::new(),
         })
     }
 
@@ -65,6 +83,8 @@ impl ClientInfo {
             hostname,
             fb,
             request_info: Some(ClientRequestInfo::new(entry_point)),
+            environment: ClientEnvironment::detect(),
+            extra: BTreeMap::new(),
         })
     }
 
@@ -76,6 +96,8 @@ impl ClientInfo {
             hostname,
             fb,
             request_info: Some(client_request_info),
+            environment: ClientEnvironment::detect(),
+            extra: BTreeMap::new(),
         })
     }
 
@@ -99,6 +121,83 @@ impl ClientInfo {
         self.request_info = Some(info);
         self
     }
+
+    /// Attach arbitrary key/value pairs (e.g. from CI environment
+    /// variables). They are serialized under an `extra` object in the JSON
+    /// representation. Keys matching built-in field names are rejected to
+    /// avoid confusion with the real fields when consumers flatten the
+    /// JSON.
+    pub fn with_extra_fields(&mut self, fields: BTreeMap<String, String>) -> Result<&mut Self> {
+        const BUILT_IN_FIELDS: &[&str] = &["hostname", "request_info", "environment", "extra"];
+        for key in fields.keys() {
+            if BUILT_IN_FIELDS.contains(&key.as_str()) {
+                bail!(
+                    "extra client info field '{}' collides with a built-in field",
+                    key
+                );
+            }
+        }
+        self.extra.extend(fields);
+        Ok(self)
+    }
+
+    /// Deterministically hash the stable identity fields of this client
+    /// (hostname, environment and host info), excluding per-request fields
+    /// like the correlator, so the same client always maps to the same value
+    /// across process restarts. Useful for consistent sampling decisions.
+    pub fn sampling_key(&self) -> u64 {
+        let mut hash = FNV_OFFSET_BASIS;
+        fnv1a_append(&mut hash, self.hostname.as_deref().unwrap_or("").as_bytes());
+        fnv1a_append(&mut hash, self.environment.as_str().as_bytes());
+        // The host info has a stable JSON representation.
+        if let Ok(fb) = serde_json::to_string(&self.fb) {
+            fnv1a_append(&mut hash, fb.as_bytes());
+        }
+        hash
+    }
+
+    /// Decide whether this client falls into a sample of the given `rate`
+    /// (a fraction in `0.0..=1.0`). The decision is consistent: the same
+    /// client always gets the same answer for the same rate.
+    pub fn should_sample(&self, rate: f64) -> bool {
+        if rate <= 0.0 {
+            return false;
+        }
+        if rate >= 1.0 {
+            return true;
+        }
+        (self.sampling_key() as f64 / u64::MAX as f64) < rate
+    }
+}
+
+/// Debug prints only non-sensitive fields and masks host/user identifiers,
+/// so accidentally logging a `ClientInfo` (ex. from its `RefCell` holder)
+/// does not leak them. Use [`ClientInfo::to_json`] when the full
+/// serialization is actually wanted.
+impl fmt::Debug for ClientInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientInfo")
+            .field("hostname", &self.hostname.as_ref().map(|_| "<redacted>"))
+            .field("fb", &"<redacted>")
+            .field("request_info", &self.request_info)
+            .field("environment", &self.environment)
+            .field("extra", &format_args!("<{} redacted entries>", self.extra.len()))
+            .finish()
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Feed `bytes` (followed by a separator so field boundaries matter) into a
+/// FNV-1a hash. FNV is used instead of the std hasher because its output is
+/// specified, so keys stay stable across process restarts and compiler
+/// versions.
+fn fnv1a_append(hash: &mut u64, bytes: &[u8]) {
+    for byte in bytes.iter().chain(std::iter::once(&0)) {
+        *hash ^= u64::from(*byte);
+        *hash = hash.wrapping_mul(FNV_PRIME);
+    }
 }
 
 #[cfg(test)]
@@ -111,4 +210,78 @@ mod tests {
         // correlator set.
         assert!(ClientInfo::from_json(r#"{"request_info":{"entry_point":"SaplingRemoteApiReplay","correlator":"vmazpnjezhjsjkay"}}"#).is_ok());
     }
+
+    #[test]
+    fn test_sampling_key_stable() {
+        let mut a = ClientInfo::default();
+        a.hostname = Some("host1".to_string());
+        a.add_request_info(ClientRequestInfo::new(ClientEntryPoint::Sapling));
+        let mut b = ClientInfo::default();
+        b.hostname = Some("host1".to_string());
+        b.add_request_info(ClientRequestInfo::new(ClientEntryPoint::EdenFs));
+
+        // Per-request fields (entry point, correlator) do not affect the key.
+        assert_eq!(a.sampling_key(), b.sampling_key());
+
+        let mut c = ClientInfo::default();
+        c.hostname = Some("host2".to_string());
+        assert_ne!(a.sampling_key(), c.sampling_key());
+
+        // Rate extremes are unconditional.
+        assert!(a.should_sample(1.0));
+        assert!(!a.should_sample(0.0));
+    }
+
+    #[test]
+    fn test_debug_redacts_identifiers() {
+        let mut info = ClientInfo::default();
+        info.hostname = Some("dev1234.example.com".to_string());
+        let mut cri = ClientRequestInfo::new(ClientEntryPoint::Sapling);
+        cri.set_main_id("user:alice".to_string());
+        info.add_request_info(cri);
+
+        // The full serialization carries the identifiers...
+        let json = info.to_json().unwrap();
+        assert!(json.contains("dev1234.example.com"));
+        assert!(json.contains("user:alice"));
+
+        // ...but Debug masks them, so accidental logging is safe.
+        let debug = format!("{:?}", info);
+        assert!(!debug.contains("dev1234.example.com"));
+        assert!(!debug.contains("user:alice"));
+        assert!(debug.contains("<redacted>"));
+        // The correlator stays visible: it is the key used to find requests.
+        let correlator = info.request_info.as_ref().unwrap().correlator.clone();
+        assert!(debug.contains(&correlator));
+    }
+
+    #[test]
+    fn test_extra_fields_roundtrip() {
+        let mut info = ClientInfo::default();
+        info.with_extra_fields(BTreeMap::from([
+            ("ci_job".to_string(), "linux-build".to_string()),
+            ("ci_run".to_string(), "42".to_string()),
+        ]))
+        .unwrap();
+
+        let json = info.to_json().unwrap();
+        assert!(json.contains(r#""extra":{"ci_job":"linux-build","ci_run":"42"}"#));
+        let parsed = ClientInfo::from_json(&json).unwrap();
+        assert_eq!(parsed.extra, info.extra);
+
+        // Keys colliding with built-in fields are rejected, and nothing is
+        // attached.
+        let before = info.clone();
+        assert!(
+            info.with_extra_fields(BTreeMap::from([(
+                "hostname".to_string(),
+                "sneaky".to_string()
+            )]))
+            .is_err()
+        );
+        assert_eq!(info, before);
+
+        // An empty map serializes to no `extra` key at all.
+        assert!(!ClientInfo::default().to_json().unwrap().contains("extra"));
+    }
 }
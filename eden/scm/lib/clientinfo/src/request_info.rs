@@ -7,7 +7,11 @@
 
 use std::cell::RefCell;
 use std::env::var;
+use std::fmt;
 use std::fmt::Display;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 
 use anyhow::anyhow;
 use anyhow::Result;
@@ -95,7 +99,7 @@ pub fn get_client_request_info_thread_local() -> Option<ClientRequestInfo> {
 
 /// ClientRequestInfo holds information that will be used for tracing the request
 /// through Source Control systems.
-#[derive(Clone, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct ClientRequestInfo {
     /// Identifier indicates who triggered the request (e.g: "user:user_id")
     /// The `main_id` is generated on the server (Mononoke) side, client side
@@ -105,8 +109,46 @@ pub struct ClientRequestInfo {
     pub entry_point: ClientEntryPoint,
     /// A random string that identifies the request
     pub correlator: String,
+    /// When the request started, captured at creation time
+    #[serde(skip)]
+    pub started_at: StartTime,
+}
+
+/// Timestamps captured when a `ClientRequestInfo` is created, used for
+/// latency attribution. The wall clock time is suitable for logging while
+/// the monotonic instant backs `elapsed()`. This is local to the process:
+/// it is not serialized, and it is ignored when comparing requests.
+#[derive(Clone, Copy, Debug)]
+pub struct StartTime {
+    /// Wall clock time when the request started
+    pub wall: SystemTime,
+    /// Monotonic time when the request started, used to compute durations
+    instant: Instant,
+}
+
+impl StartTime {
+    fn now() -> Self {
+        Self {
+            wall: SystemTime::now(),
+            instant: Instant::now(),
+        }
+    }
+}
+
+impl Default for StartTime {
+    fn default() -> Self {
+        Self::now()
+    }
 }
 
+impl PartialEq for StartTime {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for StartTime {}
+
 #[derive(Clone, Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub enum ClientEntryPoint {
     Sapling,
@@ -142,6 +184,63 @@ pub enum ClientEntryPoint {
     MononokeGitServer,
 }
 
+/// Registry of every [`ClientEntryPoint`], used to derive the set of known
+/// entry point names. When adding a variant, add it here and to
+/// [`ClientEntryPoint::name`]; the `test_known_entry_points` test catches
+/// drift between the two.
+const ALL_CLIENT_ENTRY_POINTS: &[ClientEntryPoint] = &[
+    ClientEntryPoint::Sapling,
+    ClientEntryPoint::EdenFs,
+    ClientEntryPoint::Fbclone,
+    ClientEntryPoint::ScsServer,
+    ClientEntryPoint::ScmQuery,
+    ClientEntryPoint::SaplingRemoteApi,
+    ClientEntryPoint::LandService,
+    ClientEntryPoint::LfsServer,
+    ClientEntryPoint::DerivedDataService,
+    ClientEntryPoint::DerivationWorker,
+    ClientEntryPoint::InteractiveSmartlog,
+    ClientEntryPoint::ScsClient,
+    ClientEntryPoint::Walker,
+    ClientEntryPoint::MegarepoTool,
+    ClientEntryPoint::MegarepoBacksyncer,
+    ClientEntryPoint::MegarepoForwardsyncer,
+    ClientEntryPoint::MononokeAdmin,
+    ClientEntryPoint::GitImport,
+    ClientEntryPoint::RemoteGitImport,
+    ClientEntryPoint::SaplingRemoteApiReplay,
+    ClientEntryPoint::MononokeHgSync,
+    ClientEntryPoint::MononokeCasSync,
+    ClientEntryPoint::CurlTest,
+    ClientEntryPoint::MirrorHgCommits,
+    ClientEntryPoint::StreamingClone,
+    ClientEntryPoint::ScmDaemon,
+    ClientEntryPoint::BookmarkService,
+    ClientEntryPoint::BookmarkServiceClientCli,
+    ClientEntryPoint::MononokeGitServer,
+];
+
+lazy_static! {
+    static ref KNOWN_ENTRY_POINT_NAMES: Vec<&'static str> = ALL_CLIENT_ENTRY_POINTS
+        .iter()
+        .map(ClientEntryPoint::name)
+        .collect();
+}
+
+/// Debug masks `main_id` (a user identifier) so request info can be logged
+/// safely. The entry point and correlator are non-sensitive by design: they
+/// are meant to be quoted in logs and support tickets.
+impl fmt::Debug for ClientRequestInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientRequestInfo")
+            .field("main_id", &self.main_id.as_ref().map(|_| "<redacted>"))
+            .field("entry_point", &self.entry_point)
+            .field("correlator", &self.correlator)
+            .field("started_at", &self.started_at)
+            .finish()
+    }
+}
+
 impl ClientRequestInfo {
     /// Create a new ClientRequestInfo with entry_point. The correlator will be a
     /// randomly generated string.
@@ -160,9 +259,16 @@ impl ClientRequestInfo {
             main_id: None,
             entry_point,
             correlator,
+            started_at: StartTime::now(),
         }
     }
 
+    /// Time elapsed since this ClientRequestInfo was created, measured
+    /// against a monotonic clock.
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.instant.elapsed()
+    }
+
     pub fn set_entry_point(&mut self, entry_point: ClientEntryPoint) {
         self.entry_point = entry_point;
     }
@@ -179,6 +285,13 @@ impl ClientRequestInfo {
         self.main_id.is_some()
     }
 
+    /// The names of all known entry points, as accepted by
+    /// `ClientEntryPoint::try_from`. Useful for surfacing the valid
+    /// choices when rejecting an unknown entry point name.
+    pub fn known_entry_points() -> &'static [&'static str] {
+        &KNOWN_ENTRY_POINT_NAMES
+    }
+
     pub(crate) fn generate_correlator() -> String {
         if std::env::var_os("TESTTMP").is_some() {
             "test-correlator".to_string()
@@ -192,9 +305,11 @@ impl ClientRequestInfo {
     }
 }
 
-impl Display for ClientEntryPoint {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let out = match self {
+impl ClientEntryPoint {
+    /// The canonical name of this entry point, as accepted by
+    /// `ClientEntryPoint::try_from` and reported in telemetry.
+    pub fn name(&self) -> &'static str {
+        match self {
             ClientEntryPoint::Sapling => "sapling",
             ClientEntryPoint::EdenFs => "edenfs",
             ClientEntryPoint::Fbclone => "fbclone",
@@ -224,8 +339,13 @@ impl Display for ClientEntryPoint {
             ClientEntryPoint::BookmarkService => "bookmark_service",
             ClientEntryPoint::BookmarkServiceClientCli => "bookmark_service_client_cli",
             ClientEntryPoint::MononokeGitServer => "mononoke_git_server",
-        };
-        write!(f, "{}", out)
+        }
+    }
+}
+
+impl Display for ClientEntryPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
     }
 }
 
@@ -233,38 +353,21 @@ impl TryFrom<&str> for ClientEntryPoint {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        match value {
-            "sapling" => Ok(ClientEntryPoint::Sapling),
-            "edenfs" => Ok(ClientEntryPoint::EdenFs),
-            "fbclone" => Ok(ClientEntryPoint::Fbclone),
-            "scs" => Ok(ClientEntryPoint::ScsServer),
-            "scm_query" => Ok(ClientEntryPoint::ScmQuery),
-            "eden_api" => Ok(ClientEntryPoint::SaplingRemoteApi),
-            "landservice" => Ok(ClientEntryPoint::LandService),
-            "lfs" => Ok(ClientEntryPoint::LfsServer),
-            "derived_data_service" => Ok(ClientEntryPoint::DerivedDataService),
-            "derivation_worker" => Ok(ClientEntryPoint::DerivationWorker),
-            "isl" => Ok(ClientEntryPoint::InteractiveSmartlog),
-            "scsc" => Ok(ClientEntryPoint::ScsClient),
-            "walker" => Ok(ClientEntryPoint::Walker),
-            "megarepo_tool" => Ok(ClientEntryPoint::MegarepoTool),
-            "megarepo_backsyncer" => Ok(ClientEntryPoint::MegarepoBacksyncer),
-            "megarepo_forwardsyncer" => Ok(ClientEntryPoint::MegarepoForwardsyncer),
-            "mononoke_admin" => Ok(ClientEntryPoint::MononokeAdmin),
-            "git_import" => Ok(ClientEntryPoint::GitImport),
-            "remote_git_import" => Ok(ClientEntryPoint::RemoteGitImport),
-            "eden_api_replay" => Ok(ClientEntryPoint::SaplingRemoteApiReplay),
-            "hg_sync" => Ok(ClientEntryPoint::MononokeHgSync),
-            "mononoke_re_cas_sync" => Ok(ClientEntryPoint::MononokeCasSync),
-            "curl_test" => Ok(ClientEntryPoint::CurlTest),
-            "mirror_hg_commits" => Ok(ClientEntryPoint::MirrorHgCommits),
-            "streaming_clone" => Ok(ClientEntryPoint::StreamingClone),
-            "scm_daemon" => Ok(ClientEntryPoint::ScmDaemon),
-            "bookmark_service" => Ok(ClientEntryPoint::BookmarkService),
-            "bookmark_service_client_clie" => Ok(ClientEntryPoint::BookmarkServiceClientCli),
-            "mononoke_git_server" => Ok(ClientEntryPoint::MononokeGitServer),
-            _ => Err(anyhow!("Invalid client entry point")),
+        // Historical misspelling that some callers still pass.
+        if value == "bookmark_service_client_clie" {
+            return Ok(ClientEntryPoint::BookmarkServiceClientCli);
         }
+        ALL_CLIENT_ENTRY_POINTS
+            .iter()
+            .find(|entry_point| entry_point.name() == value)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid client entry point '{}', known entry points: {}",
+                    value,
+                    KNOWN_ENTRY_POINT_NAMES.join(", "),
+                )
+            })
     }
 }
 
@@ -295,6 +398,13 @@ mod tests {
         assert!(cri.has_main_id());
     }
 
+    #[test]
+    fn test_client_request_info_elapsed() {
+        let cri = ClientRequestInfo::new(ClientEntryPoint::Sapling);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(cri.elapsed() > Duration::ZERO);
+    }
+
     #[test]
     fn test_static_client_requst_info_with_env_vars() {
         let correlator = "test1234";
@@ -305,6 +415,25 @@ mod tests {
         assert_eq!(cri.correlator, correlator.to_owned());
     }
 
+    #[test]
+    fn test_known_entry_points() {
+        let known = ClientRequestInfo::known_entry_points();
+        assert_eq!(known.len(), ALL_CLIENT_ENTRY_POINTS.len());
+
+        // Every known name parses back to its entry point, so the registry
+        // cannot drift from the parser.
+        for name in known {
+            let entry_point = ClientEntryPoint::try_from(*name).unwrap();
+            let cri = ClientRequestInfo::new(entry_point);
+            assert_eq!(cri.entry_point.name(), *name);
+        }
+
+        let err = ClientEntryPoint::try_from("no_such_entry_point").unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("no_such_entry_point"));
+        assert!(msg.contains("sapling"));
+    }
+
     #[test]
     fn test_client_entry_point() {
         assert_eq!(
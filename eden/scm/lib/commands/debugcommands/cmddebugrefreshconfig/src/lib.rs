@@ -15,11 +15,25 @@ use cmdutil::Repo;
 use cmdutil::Result;
 #[cfg(feature = "fb")]
 use configloader::hg::generate_internalconfig;
+#[cfg(feature = "fb")]
+use configloader::hg::generate_internalconfig_content;
+#[cfg(feature = "fb")]
+use configloader::hg::internalconfig_path;
 
 define_flags! {
     pub struct DebugDynamicConfigOpts {
         /// Host name to fetch a canary config from.
         canary: Option<String>,
+
+        /// Print the generated config instead of writing it to disk.
+        dry_run: bool,
+
+        /// Print a unified diff of the generated config against the
+        /// currently-installed one instead of writing it to disk.
+        diff: bool,
+
+        /// Check that the generated config parses before installing it.
+        validate: bool,
     }
 }
 
@@ -45,14 +59,65 @@ pub fn run(ctx: ReqCtx<DebugDynamicConfigOpts>, repo: Option<&mut Repo>) -> Resu
 
         let mode = FbConfigMode::default();
 
-        generate_internalconfig(
-            mode,
-            info.as_ref(),
-            repo_name,
-            ctx.opts.canary,
-            username,
-            config.get_opt("auth_proxy", "unix_socket_path")?,
-        )?;
+        if ctx.opts.diff {
+            let content = generate_internalconfig_content(
+                mode,
+                repo_name,
+                ctx.opts.canary,
+                username,
+                config.get_opt("auth_proxy", "unix_socket_path")?,
+            )?;
+            // If there is no installed config, the whole config shows up as
+            // added.
+            let current = std::fs::read_to_string(internalconfig_path(info.as_ref())?)
+                .unwrap_or_default();
+            let diff = xdiff::diff_unified_headerless(
+                &current,
+                &content,
+                xdiff::HeaderlessDiffOpts { context: 3 },
+            );
+            ctx.io().write(diff)?;
+        } else if ctx.opts.dry_run {
+            let content = generate_internalconfig_content(
+                mode,
+                repo_name,
+                ctx.opts.canary,
+                username,
+                config.get_opt("auth_proxy", "unix_socket_path")?,
+            )?;
+            ctx.io().write(content)?;
+        } else {
+            if ctx.opts.validate {
+                let content = generate_internalconfig_content(
+                    mode,
+                    repo_name.clone(),
+                    ctx.opts.canary.clone(),
+                    username.clone(),
+                    config.get_opt("auth_proxy", "unix_socket_path")?,
+                )?;
+                let mut validated = configloader::config::ConfigSet::new();
+                let errors = validated.parse(
+                    content,
+                    &configloader::config::Options::new().source("debugrefreshconfig"),
+                );
+                if !errors.is_empty() {
+                    // Don't install a config that cannot be loaded back.
+                    ctx.io().write_err(format!(
+                        "invalid generated config: {}\n",
+                        configloader::Errors(errors)
+                    ))?;
+                    return Ok(1);
+                }
+            }
+            generate_internalconfig(
+                mode,
+                info.as_ref(),
+                repo_name,
+                ctx.opts.canary,
+                username,
+                config.get_opt("auth_proxy", "unix_socket_path")?,
+            )?;
+        }
     }
     #[cfg(not(feature = "fb"))]
     let _ = (ctx, repo);
@@ -65,7 +130,10 @@ pub fn aliases() -> &'static str {
 }
 
 pub fn doc() -> &'static str {
-    "refresh the internal configuration"
+    r#"refresh the internal configuration
+
+    With --dry-run, print the configuration that would be generated without
+    writing it to disk."#
 }
 
 pub fn synopsis() -> Option<&'static str> {
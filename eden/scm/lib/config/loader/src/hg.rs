@@ -799,6 +799,51 @@ pub fn calculate_internalconfig(
     Generator::new(mode, repo_name, config_dir, user_name, proxy_sock_path)?.execute(canary)
 }
 
+/// Path of the installed internal config for the given repo (or the global
+/// one if no repo is given).
+#[cfg(feature = "fb")]
+pub fn internalconfig_path(info: Option<&RepoMinimalInfo>) -> Result<PathBuf> {
+    Ok(get_config_dir(info)?.join("hgrc.dynamic"))
+}
+
+/// Compute the content `generate_internalconfig` would write, without
+/// touching the filesystem.
+#[cfg(feature = "fb")]
+pub fn generate_internalconfig_content(
+    mode: FbConfigMode,
+    repo_name: Option<impl AsRef<str>>,
+    canary: Option<String>,
+    user_name: String,
+    proxy_sock_path: Option<String>,
+) -> Result<String> {
+    let version = ::version::VERSION;
+    let header = format!(
+        concat!(
+            "# version={}\n",
+            "# reponame={}\n",
+            "# canary={:?}\n",
+            "# username={}\n",
+            "# Generated by `hg debugrefreshconfig` - DO NOT MODIFY\n",
+        ),
+        version,
+        repo_name.as_ref().map_or("no_repo", |r| r.as_ref()),
+        canary.as_ref(),
+        &user_name,
+    );
+
+    let global_config_dir = get_config_dir(None)?;
+
+    let config = calculate_internalconfig(
+        mode,
+        global_config_dir,
+        repo_name,
+        canary,
+        user_name,
+        proxy_sock_path,
+    )?;
+    Ok(format!("{}{}", header, config.to_string()))
+}
+
 #[cfg(feature = "fb")]
 pub fn generate_internalconfig(
     mode: FbConfigMode,
@@ -833,33 +878,10 @@ pub fn generate_internalconfig(
         .into());
     }
 
-    let version = ::version::VERSION;
-    let header = format!(
-        concat!(
-            "# version={}\n",
-            "# reponame={}\n",
-            "# canary={:?}\n",
-            "# username={}\n",
-            "# Generated by `hg debugrefreshconfig` - DO NOT MODIFY\n",
-        ),
-        version,
-        repo_name.as_ref().map_or("no_repo", |r| r.as_ref()),
-        canary.as_ref(),
-        &user_name,
-    );
-
     let hgrc_path = config_dir.join("hgrc.dynamic");
-    let global_config_dir = get_config_dir(None)?;
 
-    let config = calculate_internalconfig(
-        mode,
-        global_config_dir,
-        repo_name,
-        canary,
-        user_name,
-        proxy_sock_path,
-    )?;
-    let config_str = format!("{}{}", header, config.to_string());
+    let config_str =
+        generate_internalconfig_content(mode, repo_name, canary, user_name, proxy_sock_path)?;
 
     // If the file exists and will be unchanged, just update the mtime.
     if hgrc_path.exists() && read_to_string(&hgrc_path).unwrap_or_default() == config_str {
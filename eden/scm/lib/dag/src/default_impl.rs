@@ -13,12 +13,14 @@ use futures::StreamExt;
 use futures::TryStreamExt;
 
 use crate::errors::programming;
+use crate::errors::NotFoundError;
 use crate::namedag::MemNameDag;
 use crate::nameset::hints::Hints;
 use crate::ops::DagAddHeads;
 use crate::ops::IdConvert;
 use crate::ops::IdDagAlgorithm;
 use crate::ops::Parents;
+use crate::ops::SetFormat;
 use crate::ops::ToIdSet;
 use crate::ops::ToSet;
 use crate::utils;
@@ -230,6 +232,18 @@ pub(crate) async fn parents(this: &(impl DagAlgorithm + ?Sized), set: NameSet) -
     Ok(NameSet::from_static_names(result))
 }
 
+pub(crate) async fn parent_names_batch(
+    this: &(impl DagAlgorithm + ?Sized),
+    names: Vec<VertexName>,
+) -> Result<Vec<(VertexName, Vec<VertexName>)>> {
+    let mut result = Vec::with_capacity(names.len());
+    for name in names {
+        let parents = this.parent_names(name.clone()).await?;
+        result.push((name, parents));
+    }
+    Ok(result)
+}
+
 pub(crate) async fn first_ancestor_nth(
     this: &(impl DagAlgorithm + ?Sized),
     name: VertexName,
@@ -273,6 +287,129 @@ pub(crate) async fn first_ancestors(
     this.sort(&set).await
 }
 
+pub(crate) async fn first_ancestor_line(
+    this: &(impl DagAlgorithm + ?Sized),
+    name: VertexName,
+) -> Result<Vec<VertexName>> {
+    let mut line = vec![name];
+    loop {
+        let next = line.last().expect("line is non-empty").clone();
+        match this.parent_names(next).await?.into_iter().next() {
+            Some(parent) => line.push(parent),
+            None => break,
+        }
+    }
+    Ok(line)
+}
+
+pub(crate) async fn ancestors_within(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+    max_depth: u64,
+) -> Result<NameSet> {
+    let set = set & this.all().await?;
+    let mut visited: HashSet<VertexName> = {
+        let mut names = HashSet::new();
+        let mut iter = set.iter().await?;
+        while let Some(next) = iter.next().await {
+            names.insert(next?);
+        }
+        names
+    };
+    // Bounded BFS: `current` holds the frontier at the current depth.
+    let mut current: Vec<VertexName> = visited.iter().cloned().collect();
+    for _ in 0..max_depth {
+        if current.is_empty() {
+            break;
+        }
+        let mut next_frontier = Vec::new();
+        // PERF: This is not an efficient async implementation.
+        for vertex in current {
+            for parent in this.parent_names(vertex).await? {
+                if visited.insert(parent.clone()) {
+                    next_frontier.push(parent);
+                }
+            }
+        }
+        current = next_frontier;
+    }
+    let hints = Hints::new_inherit_idmap_dag(set.hints());
+    let set = NameSet::from_iter(visited.into_iter().map(Ok), hints);
+    this.sort(&set).await
+}
+
+pub(crate) async fn to_parents_map(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+    include_external: bool,
+) -> Result<HashMap<VertexName, Vec<VertexName>>> {
+    let mut names = Vec::new();
+    let mut iter = set.iter().await?;
+    while let Some(name) = iter.next().await {
+        names.push(name?);
+    }
+    let mut result = HashMap::with_capacity(names.len());
+    for (name, parents) in this.parent_names_batch(names).await? {
+        let parents = if include_external {
+            parents
+        } else {
+            let mut kept = Vec::with_capacity(parents.len());
+            for parent in parents {
+                if set.contains(&parent).await? {
+                    kept.push(parent);
+                }
+            }
+            kept
+        };
+        result.insert(name, parents);
+    }
+    Ok(result)
+}
+
+pub(crate) async fn max_depth(this: &(impl DagAlgorithm + ?Sized), set: NameSet) -> Result<u64> {
+    // Memoized DFS over parent_names. `depths` maps a vertex to the number
+    // of edges on the longest root-to-vertex path ending at it; each vertex
+    // is computed once across the whole set.
+    let mut depths: HashMap<VertexName, u64> = HashMap::new();
+    let mut result = 0;
+    let mut iter = set.iter().await?;
+    while let Some(name) = iter.next().await {
+        let name = name?;
+        // Iterative post-order DFS to avoid deep recursion. A vertex is
+        // pushed with its parents once they are known; its depth is
+        // computed after all parents have depths.
+        let mut stack: Vec<(VertexName, Option<Vec<VertexName>>)> = vec![(name.clone(), None)];
+        while let Some((vertex, parents)) = stack.pop() {
+            if depths.contains_key(&vertex) {
+                continue;
+            }
+            match parents {
+                None => {
+                    // PERF: This is not an efficient async implementation.
+                    let parents = this.parent_names(vertex.clone()).await?;
+                    let missing: Vec<(VertexName, Option<Vec<VertexName>>)> = parents
+                        .iter()
+                        .filter(|parent| !depths.contains_key(*parent))
+                        .map(|parent| (parent.clone(), None))
+                        .collect();
+                    stack.push((vertex, Some(parents)));
+                    stack.extend(missing);
+                }
+                Some(parents) => {
+                    let depth = parents
+                        .iter()
+                        .map(|parent| depths[parent] + 1)
+                        .max()
+                        .unwrap_or(0);
+                    depths.insert(vertex, depth);
+                }
+            }
+        }
+        result = result.max(depths[&name]);
+    }
+    Ok(result)
+}
+
 pub(crate) async fn heads(this: &(impl DagAlgorithm + ?Sized), set: NameSet) -> Result<NameSet> {
     Ok(set.clone() - this.parents(set).await?)
 }
@@ -281,6 +418,67 @@ pub(crate) async fn roots(this: &(impl DagAlgorithm + ?Sized), set: NameSet) ->
     Ok(set.clone() - this.children(set).await?)
 }
 
+pub(crate) async fn descendant_frontier(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<NameSet> {
+    Ok(this.children(set.clone()).await? - set)
+}
+
+pub(crate) async fn external_parents(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<NameSet> {
+    Ok(this.parents(set.clone()).await? - set)
+}
+
+pub(crate) async fn contains_all(
+    this: &(impl DagAlgorithm + ?Sized),
+    needles: NameSet,
+    haystack: NameSet,
+) -> Result<bool> {
+    let _ = this;
+    // Fast path: compare id sets directly when both sets expose them and
+    // share an id map.
+    if let (Some((needle_ids, needle_map)), Some((hay_ids, hay_map))) = (
+        needles.to_id_set_and_id_map_in_o1(),
+        haystack.to_id_set_and_id_map_in_o1(),
+    ) {
+        if needle_map.is_compatible_with(hay_map.as_ref()) {
+            return Ok(needle_ids.difference(&hay_ids).is_empty());
+        }
+    }
+    Ok(needles.difference(&haystack).is_empty().await?)
+}
+
+pub(crate) async fn contains_any(
+    this: &(impl DagAlgorithm + ?Sized),
+    needles: NameSet,
+    haystack: NameSet,
+) -> Result<bool> {
+    let _ = this;
+    // Fast path: compare id sets directly when both sets expose them and
+    // share an id map.
+    if let (Some((needle_ids, needle_map)), Some((hay_ids, hay_map))) = (
+        needles.to_id_set_and_id_map_in_o1(),
+        haystack.to_id_set_and_id_map_in_o1(),
+    ) {
+        if needle_map.is_compatible_with(hay_map.as_ref()) {
+            return Ok(!needle_ids.intersection(&hay_ids).is_empty());
+        }
+    }
+    Ok(!needles.intersection(&haystack).is_empty().await?)
+}
+
+pub(crate) async fn roots_and_heads(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<(NameSet, NameSet)> {
+    let roots = this.roots(set.clone()).await?;
+    let heads = this.heads(set).await?;
+    Ok((roots, heads))
+}
+
 pub(crate) async fn merges(this: &(impl DagAlgorithm + ?Sized), set: NameSet) -> Result<NameSet> {
     let this = this.dag_snapshot()?;
     Ok(set.filter(Box::new(move |v: &VertexName| {
@@ -293,6 +491,20 @@ pub(crate) async fn merges(this: &(impl DagAlgorithm + ?Sized), set: NameSet) ->
     })))
 }
 
+pub(crate) async fn branch_points(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<NameSet> {
+    let this = this.dag_snapshot()?;
+    Ok(set.filter(Box::new(move |v: &VertexName| {
+        let this = this.clone();
+        Box::pin(async move {
+            let children = DagAlgorithm::children(&this, NameSet::from(v.clone())).await?;
+            Ok(children.count_slow().await? >= 2)
+        })
+    })))
+}
+
 pub(crate) async fn reachable_roots(
     this: &(impl DagAlgorithm + ?Sized),
     roots: NameSet,
@@ -304,6 +516,162 @@ pub(crate) async fn reachable_roots(
     Ok(roots.clone() & (heads.clone() | this.parents(only).await?))
 }
 
+pub(crate) async fn candidate_roots(
+    this: &(impl DagAlgorithm + ?Sized),
+    candidates: NameSet,
+    heads: NameSet,
+) -> Result<NameSet> {
+    let ancestors = this.ancestors(heads).await?;
+    Ok(candidates & this.roots(ancestors).await?)
+}
+
+pub(crate) async fn descendants_within_set(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+    bound: NameSet,
+) -> Result<NameSet> {
+    let mut result = set & bound.clone();
+    let mut frontier = result.clone();
+    loop {
+        // Unvisited children inside the bound. Vertexes outside the bound
+        // are never expanded.
+        let next = (this.children(frontier).await? & bound.clone()) - result.clone();
+        if next.is_empty().await? {
+            break;
+        }
+        result = result | next.clone();
+        frontier = next;
+    }
+    Ok(result)
+}
+
+pub(crate) async fn connected_components(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<Vec<NameSet>> {
+    // Union-find over the set, joining vertexes along parent edges that
+    // stay inside the set.
+    let mut names: Vec<VertexName> = Vec::new();
+    let mut indexes: HashMap<VertexName, usize> = HashMap::new();
+    let mut iter = set.iter().await?;
+    while let Some(name) = iter.next().await {
+        let name = name?;
+        indexes.insert(name.clone(), names.len());
+        names.push(name);
+    }
+
+    fn find(parents: &mut [usize], mut x: usize) -> usize {
+        while parents[x] != x {
+            parents[x] = parents[parents[x]];
+            x = parents[x];
+        }
+        x
+    }
+
+    let mut parents: Vec<usize> = (0..names.len()).collect();
+    for (index, name) in names.iter().enumerate() {
+        for parent_name in this.parent_names(name.clone()).await? {
+            if let Some(&parent_index) = indexes.get(&parent_name) {
+                let root1 = find(&mut parents, index);
+                let root2 = find(&mut parents, parent_index);
+                if root1 != root2 {
+                    parents[root1] = root2;
+                }
+            }
+        }
+    }
+
+    let mut components: HashMap<usize, Vec<VertexName>> = HashMap::new();
+    for index in 0..names.len() {
+        let root = find(&mut parents, index);
+        components
+            .entry(root)
+            .or_default()
+            .push(names[index].clone());
+    }
+    let mut components: Vec<Vec<VertexName>> = components.into_values().collect();
+    // Largest first; break size ties by the first vertex for determinism.
+    components.sort_unstable_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+    Ok(components
+        .into_iter()
+        .map(NameSet::from_static_names)
+        .collect())
+}
+
+pub(crate) async fn component_heads(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<NameSet> {
+    let mut result = NameSet::empty();
+    for component in this.connected_components(set).await? {
+        result = result | this.heads(component).await?;
+    }
+    Ok(result)
+}
+
+pub(crate) async fn ancestors_with_frontier(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+    stop: NameSet,
+) -> Result<(NameSet, NameSet)> {
+    let (only, stop_ancestors) = this.only_both(set, stop).await?;
+    let frontier = this.parents(only.clone()).await? & stop_ancestors;
+    Ok((only, frontier))
+}
+
+pub(crate) async fn bisect_midpoint(
+    this: &(impl DagAlgorithm + ?Sized),
+    range: NameSet,
+) -> Result<Option<VertexName>> {
+    let total = range.count().await?;
+    if total == 0 {
+        return Ok(None);
+    }
+    // Rank each vertex by the larger remaining side: `below` vertexes stay
+    // suspect if it tests bad, `total - below` if it tests good. The best
+    // midpoint minimizes that worst case. Ties prefer the larger `below`
+    // (round the split up), then the vertex name for determinism.
+    let mut best: Option<(u64, u64, VertexName)> = None;
+    let mut iter = range.iter().await?;
+    while let Some(name) = iter.next().await {
+        let name = name?;
+        // Ancestors of `name` inside the range, including `name` itself.
+        let below = (this.ancestors(NameSet::from(name.clone())).await? & range.clone())
+            .count()
+            .await?;
+        let worst_side = below.max(total - below);
+        let better = match &best {
+            None => true,
+            Some((best_worst, best_below, best_name)) => {
+                (worst_side, std::cmp::Reverse(below), &name)
+                    < (*best_worst, std::cmp::Reverse(*best_below), best_name)
+            }
+        };
+        if better {
+            best = Some((worst_side, below, name));
+        }
+    }
+    Ok(best.map(|(_, _, name)| name))
+}
+
+pub(crate) async fn reachable_heads_map(
+    this: &(impl DagAlgorithm + ?Sized),
+    roots: NameSet,
+    heads: NameSet,
+) -> Result<HashMap<VertexName, NameSet>> {
+    let mut result = HashMap::new();
+    let mut iter = roots.iter().await?;
+    while let Some(root) = iter.next().await {
+        let root = root?;
+        let reachable = this
+            .descendants(NameSet::from(root.clone()))
+            .await?
+            .intersection(&heads);
+        result.insert(root, reachable);
+    }
+    Ok(result)
+}
+
 pub(crate) async fn heads_ancestors(
     this: &(impl DagAlgorithm + ?Sized),
     set: NameSet,
@@ -331,6 +699,40 @@ pub(crate) async fn only_both(
     Ok((reachable - unreachable.clone(), unreachable))
 }
 
+pub(crate) async fn only_count(
+    this: &(impl DagAlgorithm + ?Sized),
+    reachable: NameSet,
+    unreachable: NameSet,
+) -> Result<(u64, u64)> {
+    let reachable = this.ancestors(reachable).await?;
+    let unreachable = this.ancestors(unreachable).await?;
+    let ahead = reachable.difference(&unreachable).count().await?;
+    let behind = unreachable.difference(&reachable).count().await?;
+    Ok((ahead, behind))
+}
+
+pub(crate) async fn ancestors_excluding(
+    this: &(impl DagAlgorithm + ?Sized),
+    heads: NameSet,
+    exclude: NameSet,
+) -> Result<NameSet> {
+    let only = this.only(heads.clone(), exclude).await?;
+    // Re-add the heads so they are present even if `exclude` made them
+    // unreachable. Unknown heads are filtered out by `all()`.
+    Ok(only | (heads & this.all().await?))
+}
+
+pub(crate) async fn range_inclusive_roots(
+    this: &(impl DagAlgorithm + ?Sized),
+    roots: NameSet,
+    heads: NameSet,
+) -> Result<NameSet> {
+    let range = this.range(roots.clone(), heads).await?;
+    // Re-add the roots (limited to the known graph) so they are present
+    // even if they cannot reach any head.
+    Ok(range | (roots & this.all().await?))
+}
+
 pub(crate) async fn gca_one(
     this: &(impl DagAlgorithm + ?Sized),
     set: NameSet,
@@ -373,6 +775,91 @@ pub(crate) async fn common_ancestors(
     Ok(result)
 }
 
+pub(crate) async fn ancestors_of_all(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<NameSet> {
+    // `ancestors` is inclusive, so the intersection of `ancestors({x})`
+    // is exactly `common_ancestors`.
+    this.common_ancestors(set).await
+}
+
+pub(crate) async fn format_set(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+    style: SetFormat,
+) -> Result<String> {
+    let _ = this;
+    match style {
+        SetFormat::Lines => format_set_lines(&set).await,
+        SetFormat::Summary { max } => {
+            let count = set.count_slow().await?;
+            let mut names = Vec::new();
+            let mut iter = set.iter().await?;
+            while names.len() < max {
+                match iter.next().await {
+                    Some(v) => names.push(format!("{:?}", v?)),
+                    None => break,
+                }
+            }
+            let mut result = names.join(", ");
+            let shown = names.len() as u64;
+            if count > shown {
+                result.push_str(&format!(" and {} more", count - shown));
+            }
+            Ok(result)
+        }
+        SetFormat::Range => {
+            // Use `a::b` only when the set is backed by ids forming a
+            // single contiguous span.
+            if let Some((id_set, map)) = set.to_id_set_and_id_map_in_o1() {
+                let mut spans = id_set.iter_span_asc();
+                if let (Some(span), None) = (spans.next(), spans.next()) {
+                    let low = map.vertex_name(span.low).await?;
+                    let high = map.vertex_name(span.high).await?;
+                    return Ok(format!("{:?}::{:?}", low, high));
+                }
+            }
+            format_set_lines(&set).await
+        }
+    }
+}
+
+async fn format_set_lines(set: &NameSet) -> Result<String> {
+    let mut lines = Vec::new();
+    let mut iter = set.iter().await?;
+    while let Some(v) = iter.next().await {
+        lines.push(format!("{:?}", v?));
+    }
+    Ok(lines.join("\n"))
+}
+
+pub(crate) async fn common_children(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<NameSet> {
+    let result = match set.count_slow().await? {
+        // The vacuous intersection would be `all()`. Return empty instead,
+        // which is the safer answer for callers passing a computed set.
+        0 => set,
+        1 => this.descendants(set).await?,
+        _ => {
+            // Try to reduce the size of `set`.
+            // `common_children(X)` = `common_children(heads(X))`.
+            let set = this.heads(set).await?;
+            let mut iter = set.iter().await?;
+            let mut result = this
+                .descendants(NameSet::from(iter.next().await.unwrap()?))
+                .await?;
+            while let Some(v) = iter.next().await {
+                result = result.intersection(&this.descendants(NameSet::from(v?)).await?);
+            }
+            result
+        }
+    };
+    Ok(result)
+}
+
 pub(crate) async fn is_ancestor(
     this: &(impl DagAlgorithm + ?Sized),
     ancestor: VertexName,
@@ -393,6 +880,60 @@ pub(crate) async fn is_ancestor(
     Ok(false)
 }
 
+pub(crate) async fn ancestor_count(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<u64> {
+    this.ancestors(set).await?.count_slow().await
+}
+
+pub(crate) async fn topo_sort_key(
+    this: &(impl DagAlgorithm + ?Sized),
+    name: VertexName,
+) -> Result<u64> {
+    // |ancestors(v)| strictly increases along any ancestor chain, which is
+    // all the ordering contract requires. Segmented backends override this
+    // with the assigned id, which is much cheaper to obtain.
+    this.ancestors(NameSet::from(name)).await?.count_slow().await
+}
+
+pub(crate) async fn max_topo(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<Option<VertexName>> {
+    // `sort` produces DESC order (heads first), so the maximum is the
+    // first element.
+    this.sort(&set).await?.first().await
+}
+
+pub(crate) async fn min_topo(
+    this: &(impl DagAlgorithm + ?Sized),
+    set: NameSet,
+) -> Result<Option<VertexName>> {
+    this.sort(&set).await?.last().await
+}
+
+pub(crate) async fn is_ancestor_batch(
+    this: &(impl DagAlgorithm + ?Sized),
+    pairs: Vec<(VertexName, VertexName)>,
+) -> Result<Vec<bool>> {
+    // Unknown vertexes error instead of silently reporting `false`
+    // (plain `is_ancestor` does not check the `ancestor` side).
+    let all = this.all().await?;
+    for (ancestor, descendant) in &pairs {
+        for name in [ancestor, descendant] {
+            if !all.contains(name).await? {
+                return name.not_found();
+            }
+        }
+    }
+    let mut result = Vec::with_capacity(pairs.len());
+    for (ancestor, descendant) in pairs {
+        result.push(this.is_ancestor(ancestor, descendant).await?);
+    }
+    Ok(result)
+}
+
 /// Implementation of `suggest_bisect`.
 ///
 /// This is not the default trait implementation because the extra trait bounds
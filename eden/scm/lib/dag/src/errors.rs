@@ -29,6 +29,13 @@ pub enum DagError {
     #[error("NeedSlowPath: {0}")]
     NeedSlowPath(String),
 
+    /// A flush found the on-disk graph changed by another writer after this
+    /// instance loaded it. Distinct from IO errors: the graph is intact and
+    /// it is safe to retry by reloading (or reopening) the graph, re-deriving
+    /// the heads, and flushing again.
+    #[error("FlushConflict: {0}")]
+    FlushConflict(String),
+
     /// Callsite does something wrong. For example, a "parent function" does not
     /// return reproducible results for a same vertex if called twice.
     #[error("ProgrammingError: {0}")]
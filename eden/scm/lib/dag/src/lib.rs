@@ -53,6 +53,7 @@ pub use namedag::NameDag;
 pub use namedag::NameDagBuilder;
 pub use nameset::NameSet;
 pub use ops::DagAlgorithm;
+pub use ops::SetFormat;
 pub use segment::FlatSegment;
 pub use segment::IdSegment;
 pub use segment::PreparedFlatSegments;
@@ -267,58 +267,17 @@ where
         parents: &dyn Parents,
         heads: &VertexListWithOptions,
     ) -> Result<()> {
-        if !self.pending_heads.is_empty() {
-            return programming(format!(
-                "ProgrammingError: add_heads_and_flush called with pending heads ({:?})",
-                &self.pending_heads.vertexes(),
-            ));
-        }
-
-        // Clear the VIRTUAL group. Their parents might have changed in incompatible ways.
-        self.clear_virtual_group().await?;
-
-        // Take lock.
-        //
-        // Reload meta and logs. This drops in-memory changes, which is fine because we have
-        // checked there are no in-memory changes at the beginning.
-        //
-        // Also see comments in `NameDagState::lock()`.
-        let old_version = self.state.storage_version();
-        let lock = self.state.lock()?;
-        let map_lock = self.map.lock()?;
-        let dag_lock = self.dag.lock()?;
-        self.state.reload(&lock)?;
-        let new_version = self.state.storage_version();
-        if old_version != new_version {
-            self.invalidate_snapshot();
-            self.invalidate_missing_vertex_cache();
-            self.invalidate_overlay_map()?;
-        }
-
-        self.map.reload(&map_lock)?;
-        self.dag.reload(&dag_lock)?;
-
-        // Build.
-        self.build_with_lock(parents, heads, &map_lock).await?;
-
-        // Write to disk.
-        self.map.persist(&map_lock)?;
-        self.dag.persist(&dag_lock)?;
-        self.state.persist(&lock)?;
-        drop(dag_lock);
-        drop(map_lock);
-        drop(lock);
-
-        self.persisted_id_set = self.dag.all_ids_in_groups(&Group::PERSIST)?;
-        self.maybe_recreate_virtual_group().await?;
-
-        debug_assert_eq!(self.dirty().await?.count().await?, 0);
-
-        Ok(())
+        self.add_heads_and_flush_with_verify(parents, heads, None)
+            .await
     }
 
-    /// Write in-memory DAG to disk. This will also pick up changes to
-    /// the DAG by other processes.
+    /// Write in-memory DAG to disk.
+    ///
+    /// If another writer changed the on-disk DAG after this instance
+    /// loaded it, returns [`DagError::FlushConflict`] instead of writing.
+    /// That is not an IO error: the on-disk graph is intact, and it is
+    /// safe to retry by reopening the graph, re-deriving `heads`, and
+    /// flushing again.
     ///
     /// This function re-assigns ids for vertexes. That requires the
     /// pending ids and vertexes to be non-lazy. If you're changing
@@ -344,8 +303,14 @@ where
             ));
         }
 
+        // The storage version this instance loaded. Each write below
+        // re-verifies it under the state lock right before writing and
+        // passes on the version it leaves behind, so a concurrent writer
+        // cannot sneak in between a check and a write.
+        let local_version = self.state.storage_version();
+
         // Write cached IdMap to disk.
-        self.flush_cached_idmap().await?;
+        let expected_version = self.flush_cached_idmap_with_verify(local_version).await?;
 
         // Constructs a new graph so we can copy pending data from the existing graph.
         let mut new_name_dag: Self = self.path.open()?;
@@ -354,7 +319,9 @@ where
         let non_master_heads: VertexListWithOptions = self.pending_heads.clone();
         new_name_dag.inherit_configurations_from(self);
         let heads = heads.clone().chain(non_master_heads);
-        new_name_dag.add_heads_and_flush(&parents, &heads).await?;
+        new_name_dag
+            .add_heads_and_flush_with_verify(&parents, &heads, Some(expected_version))
+            .await?;
         new_name_dag.maybe_recreate_virtual_group().await?;
 
         *self = new_name_dag;
@@ -394,6 +361,130 @@ where
     }
 }
 
+impl<IS, M, P, S> AbstractNameDag<IdDag<IS>, M, P, S>
+where
+    IS: IdDagStore + Persist + StorageVersion,
+    IdDag<IS>: TryClone + 'static,
+    M: TryClone + IdMapAssignHead + Persist + Send + Sync + 'static,
+    P: Open<OpenTarget = Self> + Send + Sync + 'static,
+    S: TryClone + StorageVersion + Persist + Send + Sync + 'static,
+{
+    /// Implementation of [`DagPersistent::add_heads_and_flush`].
+    ///
+    /// If `expected_version` is set, fail with [`DagError::FlushConflict`]
+    /// unless the on-disk storage version still matches it. The version is
+    /// re-read after taking the state lock, under which the write happens,
+    /// so the check cannot race with a concurrent writer. `flush` relies
+    /// on this to guarantee its conflict error.
+    async fn add_heads_and_flush_with_verify(
+        &mut self,
+        parents: &dyn Parents,
+        heads: &VertexListWithOptions,
+        expected_version: Option<(u64, u64)>,
+    ) -> Result<()> {
+        if !self.pending_heads.is_empty() {
+            return programming(format!(
+                "ProgrammingError: add_heads_and_flush called with pending heads ({:?})",
+                &self.pending_heads.vertexes(),
+            ));
+        }
+
+        // Clear the VIRTUAL group. Their parents might have changed in incompatible ways.
+        self.clear_virtual_group().await?;
+
+        // Take lock.
+        //
+        // Reload meta and logs. This drops in-memory changes, which is fine because we have
+        // checked there are no in-memory changes at the beginning.
+        //
+        // Also see comments in `NameDagState::lock()`.
+        let old_version = self.state.storage_version();
+        let lock = self.state.lock()?;
+        let map_lock = self.map.lock()?;
+        let dag_lock = self.dag.lock()?;
+        self.state.reload(&lock)?;
+        let new_version = self.state.storage_version();
+        if let Some(expected_version) = expected_version {
+            if new_version != expected_version {
+                return Err(DagError::FlushConflict(format!(
+                    "storage version changed from {:?} to {:?}",
+                    expected_version, new_version
+                )));
+            }
+        }
+        if old_version != new_version {
+            self.invalidate_snapshot();
+            self.invalidate_missing_vertex_cache();
+            self.invalidate_overlay_map()?;
+        }
+
+        self.map.reload(&map_lock)?;
+        self.dag.reload(&dag_lock)?;
+
+        // Build.
+        self.build_with_lock(parents, heads, &map_lock).await?;
+
+        // Write to disk.
+        self.map.persist(&map_lock)?;
+        self.dag.persist(&dag_lock)?;
+        self.state.persist(&lock)?;
+        drop(dag_lock);
+        drop(map_lock);
+        drop(lock);
+
+        self.persisted_id_set = self.dag.all_ids_in_groups(&Group::PERSIST)?;
+        self.maybe_recreate_virtual_group().await?;
+
+        debug_assert_eq!(self.dirty().await?.count().await?, 0);
+
+        Ok(())
+    }
+
+    /// Same as [`DagPersistent::flush_cached_idmap`], but fail with
+    /// [`DagError::FlushConflict`] unless the on-disk storage version
+    /// still matches `expected_version` once the state lock is taken, and
+    /// return the version left behind by the write. `flush` chains its
+    /// writes through these returned versions so its own idmap flush is
+    /// not mistaken for a concurrent writer.
+    async fn flush_cached_idmap_with_verify(
+        &self,
+        expected_version: (u64, u64),
+    ) -> Result<(u64, u64)> {
+        let mut to_insert: Vec<(AncestorPath, Vec<VertexName>)> = Vec::new();
+        std::mem::swap(&mut to_insert, &mut *self.overlay_map_paths.lock().unwrap());
+        if to_insert.is_empty() {
+            // Nothing to write. The version is unchanged.
+            return Ok(expected_version);
+        }
+
+        tracing::debug!(target: "dag::cache", "flushing cached idmap ({} items)", to_insert.len());
+        let mut new: Self = self.path.open()?;
+        let lock = new.state.lock()?;
+        let map_lock = new.map.lock()?;
+        let dag_lock = new.dag.lock()?;
+        new.state.reload(&lock)?;
+        let disk_version = new.state.storage_version();
+        if disk_version != expected_version {
+            // Nothing was written. Put the pending paths back so a retry
+            // after reopening can still flush them.
+            self.overlay_map_paths.lock().unwrap().extend(to_insert);
+            return Err(DagError::FlushConflict(format!(
+                "storage version changed from {:?} to {:?}",
+                expected_version, disk_version
+            )));
+        }
+        new.map.reload(&map_lock)?;
+        new.dag.reload(&dag_lock)?;
+        new.inherit_configurations_from(self);
+        std::mem::swap(&mut to_insert, &mut *new.overlay_map_paths.lock().unwrap());
+        new.flush_cached_idmap_with_lock(&map_lock).await?;
+
+        new.state.persist(&lock)?;
+
+        Ok(new.state.storage_version())
+    }
+}
+
 impl<IS, M, P, S> AbstractNameDag<IdDag<IS>, M, P, S>
 where
     IS: IdDagStore,
@@ -1889,6 +1980,71 @@ where
         Ok(result)
     }
 
+    /// Batch-resolve ids and names so lazy graphs fetch parents in two
+    /// round-trips instead of one per vertex.
+    async fn parent_names_batch(
+        &self,
+        names: Vec<VertexName>,
+    ) -> Result<Vec<(VertexName, Vec<VertexName>)>> {
+        let ids = self.vertex_id_batch(&names).await?;
+        let mut parent_ids_list = Vec::with_capacity(names.len());
+        let mut all_parent_ids = Vec::new();
+        for id in ids {
+            let parent_ids = self.dag().parent_ids(id?)?;
+            all_parent_ids.extend_from_slice(&parent_ids);
+            parent_ids_list.push(parent_ids);
+        }
+        let mut parent_names = self.vertex_name_batch(&all_parent_ids).await?.into_iter();
+        let mut result = Vec::with_capacity(names.len());
+        for (name, parent_ids) in names.into_iter().zip(parent_ids_list) {
+            let mut parents = Vec::with_capacity(parent_ids.len());
+            for _ in &parent_ids {
+                parents.push(parent_names.next().expect("batch length matches")?);
+            }
+            result.push((name, parents));
+        }
+        Ok(result)
+    }
+
+    /// Batch-resolve the set's vertexes so the relevant id map entries
+    /// (and, for lazy graphs, remotely fetched names) end up in local
+    /// caches before the real queries run.
+    async fn prefetch(&self, set: NameSet) -> Result<()> {
+        let mut names = Vec::new();
+        let mut iter = set.iter().await?;
+        while let Some(name) = iter.next().await {
+            names.push(name?);
+        }
+        // Per-vertex misses are not errors for a warm-up; drop the results.
+        let _ = self.vertex_id_batch(&names).await?;
+        Ok(())
+    }
+
+    /// Use the assigned id as the topological sort key. Parents are always
+    /// assigned smaller ids than their descendants, so ancestors sort first.
+    async fn topo_sort_key(&self, name: VertexName) -> Result<u64> {
+        let id = self.vertex_id(name).await?;
+        Ok(id.0)
+    }
+
+    /// Answer via the max id in the set, in O(spans).
+    async fn max_topo(&self, set: NameSet) -> Result<Option<VertexName>> {
+        let spans = self.to_id_set(&set).await?;
+        match spans.max() {
+            Some(id) => Ok(Some(self.vertex_name(id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Answer via the min id in the set, in O(spans).
+    async fn min_topo(&self, set: NameSet) -> Result<Option<VertexName>> {
+        let spans = self.to_id_set(&set).await?;
+        match spans.min() {
+            Some(id) => Ok(Some(self.vertex_name(id).await?)),
+            None => Ok(None),
+        }
+    }
+
     /// Returns a set that covers all vertexes tracked by this DAG.
     async fn all(&self) -> Result<NameSet> {
         let spans = self.dag().all()?;
@@ -1919,6 +2075,22 @@ where
         Ok(result)
     }
 
+    /// Calculates ahead/behind counts with id span arithmetic, without
+    /// enumerating vertexes.
+    async fn only_count(&self, reachable: NameSet, unreachable: NameSet) -> Result<(u64, u64)> {
+        let reachable = self.dag().ancestors(self.to_id_set(&reachable).await?)?;
+        let unreachable = self.dag().ancestors(self.to_id_set(&unreachable).await?)?;
+        let ahead = reachable.difference(&unreachable).count();
+        let behind = unreachable.difference(&reachable).count();
+        Ok((ahead, behind))
+    }
+
+    /// Count ancestors in O(spans), without materializing vertex names.
+    async fn ancestor_count(&self, set: NameSet) -> Result<u64> {
+        let spans = self.to_id_set(&set).await?;
+        Ok(self.dag().ancestors(spans)?.count())
+    }
+
     /// Like `ancestors` but follows only the first parents.
     async fn first_ancestors(&self, set: NameSet) -> Result<NameSet> {
         // If set == ancestors(set), then first_ancestors(set) == set.
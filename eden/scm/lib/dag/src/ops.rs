@@ -7,6 +7,7 @@
 
 //! DAG and Id operations (mostly traits)
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use futures::StreamExt;
@@ -28,6 +29,19 @@ use crate::Result;
 use crate::VerLink;
 use crate::VertexListWithOptions;
 
+/// Output style used by [`DagAlgorithm::format_set`].
+#[derive(Copy, Clone, Debug)]
+pub enum SetFormat {
+    /// One vertex per line.
+    Lines,
+    /// Comma-joined, showing at most `max` vertexes followed by a count of
+    /// the rest.
+    Summary { max: usize },
+    /// `a::b` if the set maps to a single contiguous id range; otherwise
+    /// falls back to `Lines`.
+    Range,
+}
+
 /// DAG related read-only algorithms.
 #[async_trait::async_trait]
 pub trait DagAlgorithm: Send + Sync {
@@ -51,9 +65,36 @@ pub trait DagAlgorithm: Send + Sync {
         default_impl::subdag(self, set).await
     }
 
+    /// Exports the parent adjacency of `set` as a map, pairing with the
+    /// `Parents` impl for `HashMap<VertexName, Vec<VertexName>>` so a
+    /// subgraph can be snapshotted and re-imported via `add_heads`.
+    ///
+    /// With `include_external` set, parents outside `set` are kept;
+    /// otherwise they are omitted, keeping the map closed under `set`.
+    async fn to_parents_map(
+        &self,
+        set: NameSet,
+        include_external: bool,
+    ) -> Result<HashMap<VertexName, Vec<VertexName>>> {
+        default_impl::to_parents_map(self, set, include_external).await
+    }
+
     /// Get ordered parent vertexes.
     async fn parent_names(&self, name: VertexName) -> Result<Vec<VertexName>>;
 
+    /// Get ordered parent vertexes for a batch of vertexes.
+    ///
+    /// Returns `(vertex, parents)` pairs matching the input order. Unlike
+    /// `parents`, each vertex's parents preserve the order reported by
+    /// `parent_names`, keeping the first/second parent distinction of
+    /// merges. Useful for rendering many vertexes at once.
+    async fn parent_names_batch(
+        &self,
+        names: Vec<VertexName>,
+    ) -> Result<Vec<(VertexName, Vec<VertexName>)>> {
+        default_impl::parent_names_batch(self, names).await
+    }
+
     /// Returns a set that covers all vertexes tracked by this DAG.
     ///
     /// Does not include VIRTUAL vertexes.
@@ -65,6 +106,31 @@ pub trait DagAlgorithm: Send + Sync {
     /// Calculates all ancestors reachable from any name from the given set.
     async fn ancestors(&self, set: NameSet) -> Result<NameSet>;
 
+    /// Calculates the number of ancestors of the given set. Segmented
+    /// backends can answer this in O(spans) without materializing vertex
+    /// names.
+    async fn ancestor_count(&self, set: NameSet) -> Result<u64> {
+        default_impl::ancestor_count(self, set).await
+    }
+
+    /// Calculates ancestors reachable from the given set within `max_depth`
+    /// parent hops.
+    ///
+    /// Depth 0 returns the input set (intersected with `all()`). This is
+    /// useful to show recent history without materializing the full
+    /// ancestor closure.
+    async fn ancestors_within(&self, set: NameSet, max_depth: u64) -> Result<NameSet> {
+        default_impl::ancestors_within(self, set, max_depth).await
+    }
+
+    /// Calculates the maximum number of edges on any root-to-vertex path
+    /// ending at a vertex in `set`: the height of the graph restricted to
+    /// `ancestors(set)`. A set containing only roots has depth 0, and an
+    /// empty set reports 0. Useful for depth metrics.
+    async fn max_depth(&self, set: NameSet) -> Result<u64> {
+        default_impl::max_depth(self, set).await
+    }
+
     /// Calculates parents of the given set.
     ///
     /// Note: Parent order is not preserved. Use [`NameDag::parent_names`]
@@ -73,6 +139,14 @@ pub trait DagAlgorithm: Send + Sync {
         default_impl::parents(self, set).await
     }
 
+    /// Calculates the immediate parents of `set` that are not in `set`
+    /// themselves: `parents(set) - set`. For a partial graph this is the
+    /// boundary of missing vertexes to fetch next - the fetch frontier of
+    /// a lazy clone.
+    async fn external_parents(&self, set: NameSet) -> Result<NameSet> {
+        default_impl::external_parents(self, set).await
+    }
+
     /// Calculates the n-th first ancestor.
     async fn first_ancestor_nth(&self, name: VertexName, n: u64) -> Result<Option<VertexName>> {
         default_impl::first_ancestor_nth(self, name, n).await
@@ -83,6 +157,14 @@ pub trait DagAlgorithm: Send + Sync {
         default_impl::first_ancestors(self, set).await
     }
 
+    /// Calculates the entire first-parent line (the "mainline") starting
+    /// at `name`: repeatedly follows the first parent until reaching a
+    /// root. The result is ordered and starts with `name` itself, so a
+    /// vertex without parents yields a single-element vector.
+    async fn first_ancestor_line(&self, name: VertexName) -> Result<Vec<VertexName>> {
+        default_impl::first_ancestor_line(self, name).await
+    }
+
     /// Calculates heads of the given set.
     async fn heads(&self, set: NameSet) -> Result<NameSet> {
         default_impl::heads(self, set).await
@@ -91,16 +173,40 @@ pub trait DagAlgorithm: Send + Sync {
     /// Calculates children of the given set.
     async fn children(&self, set: NameSet) -> Result<NameSet>;
 
+    /// Calculates the immediate children of `set` that are not in `set`
+    /// themselves: `children(set) - set`, the first layer of the forward
+    /// cone `descendants(set) - set`. Useful for impact analysis, as the
+    /// "directly affected" vertexes.
+    async fn descendant_frontier(&self, set: NameSet) -> Result<NameSet> {
+        default_impl::descendant_frontier(self, set).await
+    }
+
     /// Calculates roots of the given set.
     async fn roots(&self, set: NameSet) -> Result<NameSet> {
         default_impl::roots(self, set).await
     }
 
+    /// Calculates roots and heads of the given set in one call.
+    ///
+    /// Roots have no parent in the set; heads have no child in the set.
+    /// Backends that can compute both from a single traversal should
+    /// override this to avoid walking the set twice.
+    async fn roots_and_heads(&self, set: NameSet) -> Result<(NameSet, NameSet)> {
+        default_impl::roots_and_heads(self, set).await
+    }
+
     /// Calculates merges of the selected set (vertexes with >=2 parents).
     async fn merges(&self, set: NameSet) -> Result<NameSet> {
         default_impl::merges(self, set).await
     }
 
+    /// Calculates branch points of the selected set (vertexes with >=2
+    /// children in the whole DAG). The dual of `merges`: where history
+    /// diverged rather than where it reconverged.
+    async fn branch_points(&self, set: NameSet) -> Result<NameSet> {
+        default_impl::branch_points(self, set).await
+    }
+
     /// Calculates one "greatest common ancestor" of the given set.
     ///
     /// If there are no common ancestors, return None.
@@ -121,11 +227,87 @@ pub trait DagAlgorithm: Send + Sync {
         default_impl::common_ancestors(self, set).await
     }
 
+    /// Calculates vertexes that are ancestors of every vertex in `set`:
+    /// the intersection of `ancestors({x})` over each member `x`. Compare
+    /// with `ancestors`, which is the union (ancestors of *any* member).
+    ///
+    /// Since `ancestors` includes the vertex itself, members of `set` can
+    /// show up in the result (ex. with `X::Y`, `ancestors_of_all(X + Y)`
+    /// contains `X`). `common_ancestors` uses the same inclusive
+    /// definition, so this is an alias for it whose name spells out the
+    /// any/all distinction.
+    async fn ancestors_of_all(&self, set: NameSet) -> Result<NameSet> {
+        default_impl::ancestors_of_all(self, set).await
+    }
+
+    /// Calculates all common children of the given set: vertexes that are
+    /// descendants of every vertex in `set`.
+    ///
+    /// An empty `set` returns an empty set, not `all()` (the vacuous
+    /// intersection), for safety.
+    async fn common_children(&self, set: NameSet) -> Result<NameSet> {
+        default_impl::common_children(self, set).await
+    }
+
+    /// Render `set` as a human-readable string in the given [`SetFormat`]
+    /// style, suitable for CLI output.
+    async fn format_set(&self, set: NameSet, style: SetFormat) -> Result<String> {
+        default_impl::format_set(self, set, style).await
+    }
+
+    /// Tests if every vertex in `needles` is also in `haystack`.
+    ///
+    /// An empty `needles` returns `true`.
+    async fn contains_all(&self, needles: NameSet, haystack: NameSet) -> Result<bool> {
+        default_impl::contains_all(self, needles, haystack).await
+    }
+
+    /// Tests if any vertex in `needles` is also in `haystack`.
+    ///
+    /// An empty `needles` returns `false`.
+    async fn contains_any(&self, needles: NameSet, haystack: NameSet) -> Result<bool> {
+        default_impl::contains_any(self, needles, haystack).await
+    }
+
     /// Tests if `ancestor` is an ancestor of `descendant`.
     async fn is_ancestor(&self, ancestor: VertexName, descendant: VertexName) -> Result<bool> {
         default_impl::is_ancestor(self, ancestor, descendant).await
     }
 
+    /// Tests ancestry for a batch of `(ancestor, descendant)` pairs,
+    /// preserving order. A pair whose vertexes are equal is `true`.
+    ///
+    /// Errors out if any vertex in any pair is unknown, instead of
+    /// silently reporting `false`. Backends that can group the queries by
+    /// shared ancestor sets should override this to avoid re-walking per
+    /// pair.
+    async fn is_ancestor_batch(&self, pairs: Vec<(VertexName, VertexName)>) -> Result<Vec<bool>> {
+        default_impl::is_ancestor_batch(self, pairs).await
+    }
+
+    /// Returns a numeric key for `name` consistent with `sort`'s topological
+    /// ordering: for any ancestor/descendant pair, the ancestor's key is
+    /// strictly smaller. Useful to merge-sort results from multiple queries.
+    ///
+    /// Keys are only comparable within one dag. Segmented backends return
+    /// the assigned id; the default derives a generation-based key.
+    async fn topo_sort_key(&self, name: VertexName) -> Result<u64> {
+        default_impl::topo_sort_key(self, name).await
+    }
+
+    /// Returns the vertex in `set` with the greatest topological order, or
+    /// `None` if `set` is empty. Ties are broken by `sort`'s deterministic
+    /// ordering. Segmented backends can answer via the max id in the set.
+    async fn max_topo(&self, set: NameSet) -> Result<Option<VertexName>> {
+        default_impl::max_topo(self, set).await
+    }
+
+    /// Returns the vertex in `set` with the least topological order, or
+    /// `None` if `set` is empty. See `max_topo`.
+    async fn min_topo(&self, set: NameSet) -> Result<Option<VertexName>> {
+        default_impl::min_topo(self, set).await
+    }
+
     /// Calculates "heads" of the ancestors of the given set. That is,
     /// Find Y, which is the smallest subset of set X, where `ancestors(Y)` is
     /// `ancestors(X)`.
@@ -143,6 +325,13 @@ pub trait DagAlgorithm: Send + Sync {
     /// Calculates the "dag range" - vertexes reachable from both sides.
     async fn range(&self, roots: NameSet, heads: NameSet) -> Result<NameSet>;
 
+    /// Like `range`, but the requested `roots` (limited to known vertexes)
+    /// are always part of the result, even if they cannot reach any head.
+    /// Useful for UIs that always render the requested endpoints.
+    async fn range_inclusive_roots(&self, roots: NameSet, heads: NameSet) -> Result<NameSet> {
+        default_impl::range_inclusive_roots(self, roots, heads).await
+    }
+
     /// Calculates `ancestors(reachable) - ancestors(unreachable)`.
     async fn only(&self, reachable: NameSet, unreachable: NameSet) -> Result<NameSet> {
         default_impl::only(self, reachable, unreachable).await
@@ -160,9 +349,49 @@ pub trait DagAlgorithm: Send + Sync {
         default_impl::only_both(self, reachable, unreachable).await
     }
 
+    /// Calculates the ahead/behind counts between the two sets:
+    /// `(|only(reachable, unreachable)|, |only(unreachable, reachable)|)`.
+    /// Segmented backends answer this with span arithmetic without
+    /// enumerating vertexes.
+    async fn only_count(&self, reachable: NameSet, unreachable: NameSet) -> Result<(u64, u64)> {
+        default_impl::only_count(self, reachable, unreachable).await
+    }
+
+    /// Calculates `only(set, stop)` together with its stopping frontier:
+    /// the immediate parents of the result that fall inside
+    /// `ancestors(stop)`. Feeding the frontier back as the next `set`
+    /// resumes the walk where it stopped, which supports incremental
+    /// fetches. Returns `(only, frontier)`.
+    async fn ancestors_with_frontier(
+        &self,
+        set: NameSet,
+        stop: NameSet,
+    ) -> Result<(NameSet, NameSet)> {
+        default_impl::ancestors_with_frontier(self, set, stop).await
+    }
+
+    /// Calculates `only(heads, exclude)` but with `heads` themselves always
+    /// included, even if they are ancestors of `exclude`. Useful for "show
+    /// my branch even if its tip was already merged" cases.
+    async fn ancestors_excluding(&self, heads: NameSet, exclude: NameSet) -> Result<NameSet> {
+        default_impl::ancestors_excluding(self, heads, exclude).await
+    }
+
     /// Calculates the descendants of the given set.
     async fn descendants(&self, set: NameSet) -> Result<NameSet>;
 
+    /// Calculates descendants of `set` that lie within `bound`, walking
+    /// forward only through vertexes inside `bound` so out-of-bound
+    /// regions of the forward cone are never expanded.
+    ///
+    /// When every path between two `bound` vertexes stays inside `bound`
+    /// (e.g. `bound` is the draft set, whose complement is
+    /// ancestor-closed), this equals `descendants(set) & bound`. A path
+    /// that leaves `bound` is not followed back in.
+    async fn descendants_within_set(&self, set: NameSet, bound: NameSet) -> Result<NameSet> {
+        default_impl::descendants_within_set(self, set, bound).await
+    }
+
     /// Calculates `roots` that are reachable from `heads` without going
     /// through other `roots`. For example, given the following graph:
     ///
@@ -193,6 +422,91 @@ pub trait DagAlgorithm: Send + Sync {
         default_impl::reachable_roots(self, roots, heads).await
     }
 
+    /// Calculates the roots of `ancestors(heads)`, restricted to
+    /// `candidates`: `candidates & roots(ancestors(heads))`.
+    ///
+    /// Unlike `reachable_roots` there is no "without going through other
+    /// roots" constraint: a candidate is only returned if it is a root of
+    /// the ancestor closure itself, and other candidates on the path do
+    /// not mask it. On the graph above, `candidate_roots([A, B, C], [F])`
+    /// returns `[A]` (`C` has a parent in the closure), while
+    /// `reachable_roots` returns `[A, C]`.
+    async fn candidate_roots(&self, candidates: NameSet, heads: NameSet) -> Result<NameSet> {
+        default_impl::candidate_roots(self, candidates, heads).await
+    }
+
+    /// Partitions `set` into connected components of its induced subgraph,
+    /// considering only parent edges between vertexes inside the set.
+    /// Components are returned largest-first; a connected set returns a
+    /// one-element vector. Useful for rendering and for validating inputs
+    /// of algorithms that assume connectivity.
+    async fn connected_components(&self, set: NameSet) -> Result<Vec<NameSet>> {
+        default_impl::connected_components(self, set).await
+    }
+
+    /// Calculates the `heads` of each connected component of `set`, unioned
+    /// together: a minimal set of tips spanning all components. A component
+    /// with multiple tips contributes all of them. Cheaper to render than
+    /// the full components when components are large.
+    async fn component_heads(&self, set: NameSet) -> Result<NameSet> {
+        default_impl::component_heads(self, set).await
+    }
+
+    /// For each vertex in `roots`, calculates the subset of `heads` that is
+    /// reachable from it (i.e. has it as an ancestor). Useful for "where did
+    /// this commit land" queries. This generalizes `reachable_roots`.
+    ///
+    /// A root reaching no head maps to an empty set.
+    async fn reachable_heads_map(
+        &self,
+        roots: NameSet,
+        heads: NameSet,
+    ) -> Result<HashMap<VertexName, NameSet>> {
+        default_impl::reachable_heads_map(self, roots, heads).await
+    }
+
+    /// Filters `set` down to vertexes whose `key` falls within `range`,
+    /// preserving the set order. The dag does not store commit metadata;
+    /// `key` is the caller's lookup (ex. a commit date or generation
+    /// number), which generalizes date slicing without baking metadata into
+    /// the dag. Vertexes whose key is `None` are excluded.
+    async fn filter_by<K, F>(
+        &self,
+        set: NameSet,
+        key: F,
+        range: std::ops::RangeInclusive<K>,
+    ) -> Result<NameSet>
+    where
+        K: Ord + Send + Sync,
+        F: Fn(&VertexName) -> Result<Option<K>> + Send + Sync,
+        Self: Sized,
+    {
+        // Pure set filtering; needs no graph access.
+        let mut result: Vec<VertexName> = Vec::new();
+        let mut iter = set.iter().await?;
+        while let Some(name) = iter.next().await {
+            let name = name?;
+            if let Some(key) = key(&name)? {
+                if range.contains(&key) {
+                    result.push(name);
+                }
+            }
+        }
+        Ok(NameSet::from_static_names(result))
+    }
+
+    /// Picks a vertex roughly in the middle of `range` by ancestor count:
+    /// the vertex whose ancestor count within the range is closest to half
+    /// the range size, minimizing the larger remaining side whichever way
+    /// the test goes (the standard bisect heuristic). For a linear range
+    /// this is the middle vertex. Returns `None` for an empty range.
+    ///
+    /// See `suggest_bisect` for the full bisect protocol with good / bad /
+    /// skip tracking.
+    async fn bisect_midpoint(&self, range: NameSet) -> Result<Option<VertexName>> {
+        default_impl::bisect_midpoint(self, range).await
+    }
+
     /// Suggest the next place to test during a bisect.
     ///
     /// - `(roots, heads)` are either `(good, bad)` or `(bad, good)`.
@@ -210,6 +524,15 @@ pub trait DagAlgorithm: Send + Sync {
         skip: NameSet,
     ) -> Result<(Option<VertexName>, NameSet, NameSet)>;
 
+    /// Warms backend caches for `set` ahead of a batch of queries, loading
+    /// the relevant backing data (ex. segments and id map entries) into
+    /// memory. Purely a performance hint: query results are unchanged
+    /// whether or not this is called. The default implementation is a
+    /// no-op; backends with lazy or on-disk state can override it.
+    async fn prefetch(&self, _set: NameSet) -> Result<()> {
+        Ok(())
+    }
+
     /// Vertexes buffered in memory, not yet written to disk.
     ///
     /// Does not include VIRTUAL vertexes.
@@ -568,6 +891,30 @@ pub trait IdConvert: PrefixLookup + Sync {
         Ok(ids)
     }
 
+    /// Test if an `id` is mapped, without materializing the [`VertexName`].
+    ///
+    /// Backends with id-range metadata can answer this more cheaply than
+    /// `vertex_name`. The default translates a not-found error into `false`.
+    async fn contains_vertex_id(&self, id: Id) -> Result<bool> {
+        match self.vertex_name(id).await {
+            Ok(_) => Ok(true),
+            Err(crate::errors::DagError::IdNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Convert `ids` to [`VertexName`]s, preserving order.
+    ///
+    /// Unlike `vertex_name_batch`, any unknown id fails the whole call with
+    /// the same error the single-id `vertex_name` path would produce.
+    async fn vertex_names(&self, ids: Vec<Id>) -> Result<Vec<VertexName>> {
+        let mut names = Vec::with_capacity(ids.len());
+        for name in self.vertex_name_batch(&ids).await? {
+            names.push(name?);
+        }
+        Ok(names)
+    }
+
     /// Identity of the map.
     fn map_id(&self) -> &str;
 
@@ -575,6 +922,15 @@ pub trait IdConvert: PrefixLookup + Sync {
     ///
     /// For performance, this does not include changes to the VIRTUAL group.
     fn map_version(&self) -> &VerLink;
+
+    /// Test if this map is compatible with `other`: both have the same,
+    /// non-empty identity, so `Id`s from one can be interpreted by the
+    /// other. Centralizes the comparison rule so future changes (e.g.
+    /// versioned map ids) only need to touch one place.
+    fn is_compatible_with(&self, other: &dyn IdConvert) -> bool {
+        let map_id = self.map_id();
+        !map_id.is_empty() && map_id == other.map_id()
+    }
 }
 
 /// Integrity check functions.
@@ -650,6 +1006,21 @@ where
 pub trait ToIdSet {
     /// Converts [`NameSet`] to [`IdSet`].
     async fn to_id_set(&self, set: &NameSet) -> Result<IdSet>;
+
+    /// Converts [`NameSet`] to id spans, invoking `callback` with each
+    /// `(low, high)` span in descending order, so very large sets (e.g.
+    /// `all()`) can be processed without holding the whole [`IdSet`].
+    /// Id-backed sets stream their existing spans directly.
+    async fn to_id_set_spans(
+        &self,
+        set: &NameSet,
+        callback: &mut (dyn FnMut(Id, Id) + Send),
+    ) -> Result<()> {
+        for span in self.to_id_set(set).await?.iter_span_desc() {
+            callback(span.low, span.high);
+        }
+        Ok(())
+    }
 }
 
 pub trait ToSet {
@@ -761,6 +1132,42 @@ impl<T: IdConvert + IdMapSnapshot> ToIdSet for T {
         }
         Ok(spans)
     }
+
+    /// Streams id spans to `callback` without cloning the whole [`IdSet`]
+    /// when the set is already backed by ids.
+    async fn to_id_set_spans(
+        &self,
+        set: &NameSet,
+        callback: &mut (dyn FnMut(Id, Id) + Send),
+    ) -> Result<()> {
+        let version = set.hints().id_map_version();
+
+        // Fast path: stream spans from IdStaticSet directly.
+        if let Some(set) = set.as_any().downcast_ref::<IdStaticSet>() {
+            if None < version && version <= Some(self.map_version()) {
+                tracing::debug!(target: "dag::algo::to_id_set_spans", "{:6?} (fast path)", set);
+                for span in set.spans.iter_span_desc() {
+                    callback(span.low, span.high);
+                }
+                return Ok(());
+            }
+        }
+
+        // Fast path: flatten to IdStaticSet. This works for UnionSet(...) cases.
+        if let Some(set) = set.specialized_flatten_id() {
+            tracing::debug!(target: "dag::algo::to_id_set_spans", "{:6?} (fast path 2)", set);
+            for span in set.spans.iter_span_desc() {
+                callback(span.low, span.high);
+            }
+            return Ok(());
+        }
+
+        // Slow path: materialize via to_id_set, then report its spans.
+        for span in self.to_id_set(set).await?.iter_span_desc() {
+            callback(span.low, span.high);
+        }
+        Ok(())
+    }
 }
 
 impl IdMapSnapshot for Arc<dyn IdConvert + Send + Sync> {
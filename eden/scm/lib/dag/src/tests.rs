@@ -16,6 +16,7 @@ use crate::nameset::SyncNameSetQuery;
 use crate::ops::DagAddHeads;
 use crate::ops::DagPersistent;
 use crate::ops::ImportAscii;
+use crate::ops::SetFormat;
 #[cfg(feature = "render")]
 use crate::render::render_namedag;
 use crate::DagAlgorithm;
@@ -67,6 +68,8 @@ use crate::iddag::FirstAncestorConstraint;
 use crate::namedag::MemNameDag;
 use crate::ops::IdConvert;
 #[cfg(test)]
+use crate::ops::ToIdSet;
+#[cfg(test)]
 use crate::protocol::Process;
 #[cfg(test)]
 use crate::protocol::RequestLocationToName;
@@ -403,6 +406,740 @@ fn test_import_ascii_with_vertex_fn() {
     assert_eq!(expand(r(dag.all()).unwrap()), "A0 B0 C0");
 }
 
+#[test]
+fn test_roots_and_heads() {
+    let ascii = r#"
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let (roots, heads) = r(dag.roots_and_heads(nameset("A B C D"))).unwrap();
+    assert_eq!(expand(roots), "A");
+    assert_eq!(expand(heads), "D");
+}
+
+#[test]
+fn test_format_set() {
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D");
+    let all = r(dag.ancestors(nameset("D"))).unwrap();
+
+    // One vertex per line, in iteration (descending) order.
+    assert_eq!(
+        r(dag.format_set(all.clone(), SetFormat::Lines)).unwrap(),
+        "D\nC\nB\nA"
+    );
+
+    // Summary caps the number of vertexes shown.
+    assert_eq!(
+        r(dag.format_set(all.clone(), SetFormat::Summary { max: 2 })).unwrap(),
+        "D, C and 2 more"
+    );
+
+    // A contiguous id range renders in `a::b` notation.
+    assert_eq!(
+        r(dag.format_set(all.clone(), SetFormat::Range)).unwrap(),
+        "A::D"
+    );
+
+    // A sparse set (two id spans) falls back to listing.
+    let b = r(dag.sort(&nameset("B"))).unwrap();
+    let sparse = all.difference(&b);
+    assert_eq!(
+        r(dag.format_set(sparse, SetFormat::Range)).unwrap(),
+        "D\nC\nA"
+    );
+}
+
+#[test]
+fn test_common_children() {
+    // Two branches (B, C) reconverge at the merge D.
+    let ascii = r#"
+        E
+        |
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    assert_eq!(expand(r(dag.common_children(nameset("B C"))).unwrap()), "D E");
+    assert_eq!(expand(r(dag.common_children(nameset("A"))).unwrap()), "A B C D E");
+    // Empty input returns empty, not all().
+    assert_eq!(expand(r(dag.common_children(nameset(""))).unwrap()), "");
+}
+
+#[test]
+fn test_ancestor_count() {
+    // Two branches (B, C) reconverge at the merge D.
+    let ascii = r#"
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+
+    // The count always equals the length of the ancestors set.
+    for names in ["A", "B", "D", "B C"] {
+        let set = nameset(names);
+        let expected = r(r(dag.ancestors(set.clone())).unwrap().count_slow()).unwrap();
+        assert_eq!(r(dag.ancestor_count(set)).unwrap(), expected);
+    }
+    assert_eq!(r(dag.ancestor_count(nameset("D"))).unwrap(), 4);
+    assert_eq!(r(dag.ancestor_count(nameset("B C"))).unwrap(), 3);
+}
+
+#[test]
+fn test_topo_sort_key() {
+    // Two branches (B, C) reconverge at the merge D.
+    let ascii = r#"
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let key = |name: &str| r(dag.topo_sort_key(VertexName::copy_from(name.as_bytes()))).unwrap();
+
+    // Ancestors always have strictly smaller keys than descendants.
+    assert!(key("A") < key("B"));
+    assert!(key("A") < key("C"));
+    assert!(key("B") < key("D"));
+    assert!(key("C") < key("D"));
+
+    // The keys agree with `sort`, which is topologically descending.
+    let sorted = r(dag.sort(&nameset("A B C D"))).unwrap();
+    let mut iter = r(sorted.iter()).unwrap();
+    let mut prev: Option<u64> = None;
+    while let Some(name) = r(iter.next()) {
+        let current = r(dag.topo_sort_key(name.unwrap())).unwrap();
+        if let Some(prev) = prev {
+            assert!(prev > current);
+        }
+        prev = Some(current);
+    }
+}
+
+#[test]
+fn test_max_min_topo() {
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D");
+    let v = |name: &str| VertexName::copy_from(name.as_bytes());
+
+    // On a linear graph the maximum is the head and the minimum is the root.
+    assert_eq!(r(dag.max_topo(nameset("A B C D"))).unwrap(), Some(v("D")));
+    assert_eq!(r(dag.min_topo(nameset("A B C D"))).unwrap(), Some(v("A")));
+
+    // Subsets respect the same order.
+    assert_eq!(r(dag.max_topo(nameset("B C"))).unwrap(), Some(v("C")));
+    assert_eq!(r(dag.min_topo(nameset("B C"))).unwrap(), Some(v("B")));
+
+    // An empty set has no extremes.
+    assert_eq!(r(dag.max_topo(nameset(""))).unwrap(), None);
+    assert_eq!(r(dag.min_topo(nameset(""))).unwrap(), None);
+}
+
+#[test]
+fn test_first_ancestor_line() {
+    // D is a merge; its first parent is B.
+    let ascii = r#"
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let v = |name: &str| VertexName::copy_from(name.as_bytes());
+    let line = |name: &str| r(dag.first_ancestor_line(v(name))).unwrap();
+
+    // Only first parents are followed through the merge.
+    assert_eq!(line("D"), vec![v("D"), v("B"), v("A")]);
+    assert_eq!(line("C"), vec![v("C"), v("A")]);
+    // A root yields just itself.
+    assert_eq!(line("A"), vec![v("A")]);
+}
+
+#[test]
+fn test_descendants_within_set() {
+    // E is a fork of the main line at B.
+    let ascii = r#"
+        D E
+        | |
+        C |
+        |/
+        B
+        |
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let within = |set: &str, bound: &str| {
+        expand(r(dag.descendants_within_set(nameset(set), nameset(bound))).unwrap())
+    };
+
+    // The bound trims part of the forward cone.
+    assert_eq!(within("B", "B C E"), "B C E");
+    // A bound with a gap stops the walk: D is reachable only through the
+    // excluded C.
+    assert_eq!(within("B", "B D E"), "B E");
+    // Starting points outside the bound contribute nothing.
+    assert_eq!(within("A", "C D"), "");
+}
+
+#[test]
+fn test_connected_components() {
+    // Two islands: A-B-C and X-Y.
+    let dag = from_ascii(from_ascii(MemNameDag::new(), "A---B---C"), "X---Y");
+
+    // The whole graph splits into two components, largest first.
+    let components = r(dag.connected_components(r(dag.all()).unwrap())).unwrap();
+    assert_eq!(components.len(), 2);
+    assert_eq!(expand(components[0].clone()), "A B C");
+    assert_eq!(expand(components[1].clone()), "X Y");
+
+    // A subset can split even within one island: only edges inside the
+    // set count.
+    let components = r(dag.connected_components(nameset("A C X"))).unwrap();
+    assert_eq!(components.len(), 3);
+
+    // A connected set is a single component.
+    let components = r(dag.connected_components(nameset("B C"))).unwrap();
+    assert_eq!(components.len(), 1);
+    assert_eq!(expand(components[0].clone()), "B C");
+}
+
+#[test]
+fn test_component_heads() {
+    // Two islands: A-B-C and X-Y.
+    let dag = from_ascii(from_ascii(MemNameDag::new(), "A---B---C"), "X---Y");
+
+    // Exactly one tip per island.
+    let heads = r(dag.component_heads(r(dag.all()).unwrap())).unwrap();
+    assert_eq!(expand(heads), "C Y");
+
+    // Subsets follow the induced subgraph: B is the tip of {A, B}.
+    let heads = r(dag.component_heads(nameset("A B Y"))).unwrap();
+    assert_eq!(expand(heads), "B Y");
+}
+
+#[test]
+fn test_ancestors_with_frontier() {
+    // A linear walk stops at B and records it as the frontier.
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D---E");
+    let walk = |set: &str, stop: &str| {
+        let (only, frontier) = r(dag.ancestors_with_frontier(nameset(set), nameset(stop))).unwrap();
+        (expand(only), expand(frontier))
+    };
+    assert_eq!(walk("E", "B"), ("C D E".to_string(), "B".to_string()));
+
+    // Resuming from the frontier covers the rest of the history.
+    assert_eq!(walk("B", ""), ("A B".to_string(), "".to_string()));
+
+    // Branchy case: the frontier is exactly the boundary vertexes of both
+    // branches.
+    let ascii = r#"
+        D
+        |
+        C F
+        | |
+        B E
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let (only, frontier) =
+        r(dag.ancestors_with_frontier(nameset("D F"), nameset("C E"))).unwrap();
+    assert_eq!(expand(only), "D F");
+    assert_eq!(expand(frontier), "C E");
+}
+
+#[test]
+fn test_filter_by() {
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D---E");
+    // Synthetic metadata, ex. commit dates. D has none.
+    let key = |name: &VertexName| -> crate::Result<Option<i64>> {
+        Ok(match name.as_ref() {
+            b"A" => Some(10),
+            b"B" => Some(20),
+            b"C" => Some(30),
+            b"E" => Some(50),
+            _ => None,
+        })
+    };
+
+    // A middle band selects B and C; D is excluded because its key is
+    // `None`, not because of the range.
+    let set = r(dag.filter_by(r(dag.all()).unwrap(), key, 20..=40)).unwrap();
+    assert_eq!(expand(set), "B C");
+
+    // The full band still excludes keyless vertexes.
+    let set = r(dag.filter_by(r(dag.all()).unwrap(), key, i64::MIN..=i64::MAX)).unwrap();
+    assert_eq!(expand(set), "A B C E");
+}
+
+#[test]
+fn test_bisect_midpoint() {
+    // A linear chain of odd length bisects to the exact middle.
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D---E");
+    let midpoint = r(dag.bisect_midpoint(r(dag.all()).unwrap())).unwrap();
+    assert_eq!(midpoint, Some("C".into()));
+
+    // A sub-range bisects within itself.
+    let midpoint = r(dag.bisect_midpoint(nameset("B C D"))).unwrap();
+    assert_eq!(midpoint, Some("C".into()));
+
+    // Branchy range: D-C-B and F-E forked over A. C splits 3/3, which is
+    // the best worst-case either way the test goes.
+    let ascii = r#"
+        D
+        |
+        C F
+        | |
+        B E
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let midpoint = r(dag.bisect_midpoint(r(dag.all()).unwrap())).unwrap();
+    assert_eq!(midpoint, Some("C".into()));
+
+    // An empty range has no midpoint.
+    let midpoint = r(dag.bisect_midpoint(nameset(""))).unwrap();
+    assert_eq!(midpoint, None);
+}
+
+#[test]
+fn test_candidate_roots() {
+    // The diamond documented on `reachable_roots`.
+    let ascii = r#"
+        F
+        |\
+        C E
+        | |
+        B D
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+
+    // Only A is a root of ancestors(F); C is masked by having a parent in
+    // the closure. `reachable_roots` also returns C since F reaches it
+    // without going through another root.
+    assert_eq!(
+        expand(r(dag.candidate_roots(nameset("A B C"), nameset("F"))).unwrap()),
+        "A"
+    );
+    assert_eq!(
+        expand(r(dag.reachable_roots(nameset("A B C"), nameset("F"))).unwrap()),
+        "A C"
+    );
+
+    // No candidate is a root of the closure, while `reachable_roots`
+    // still returns C.
+    assert_eq!(
+        expand(r(dag.candidate_roots(nameset("B C"), nameset("F"))).unwrap()),
+        ""
+    );
+    assert_eq!(
+        expand(r(dag.reachable_roots(nameset("B C"), nameset("F"))).unwrap()),
+        "C"
+    );
+}
+
+#[test]
+fn test_only_count() {
+    // D and F are two forked branches over A.
+    let ascii = r#"
+        D
+        |
+        C F
+        | |
+        B E
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let count = |reachable: &str, unreachable: &str| {
+        r(dag.only_count(nameset(reachable), nameset(unreachable))).unwrap()
+    };
+
+    // D is 3 ahead (B C D) and 2 behind (E F) of F.
+    assert_eq!(count("D", "F"), (3, 2));
+    // Swapping the sets swaps the counts.
+    assert_eq!(count("F", "D"), (2, 3));
+    // An ancestor is only behind.
+    assert_eq!(count("B", "D"), (0, 2));
+    assert_eq!(count("D", "D"), (0, 0));
+}
+
+#[test]
+fn test_descendant_frontier() {
+    // B and C are children of A; D merges them; E continues D.
+    let ascii = r#"
+        E
+        |
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let frontier = |set: &str| expand(r(dag.descendant_frontier(nameset(set))).unwrap());
+
+    // A single vertex maps to exactly its direct children outside the set.
+    assert_eq!(frontier("A"), "B C");
+    assert_eq!(frontier("D"), "E");
+    // Children inside the set are excluded; only the first outside layer
+    // remains. E (a grandchild of B) is not directly affected by "A B".
+    assert_eq!(frontier("A B"), "C D");
+    // A head has no descendants.
+    assert_eq!(frontier("E"), "");
+}
+
+#[test]
+fn test_external_parents() {
+    // D merges B and C; E continues D.
+    let ascii = r#"
+        E
+        |
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let external = |set: &str| expand(r(dag.external_parents(nameset(set))).unwrap());
+
+    // The set's roots have parents outside the set; exactly those are
+    // returned.
+    assert_eq!(external("D E"), "B C");
+    assert_eq!(external("B C"), "A");
+    // Parents inside the set are not reported.
+    assert_eq!(external("B D"), "A C");
+    // A set closed under ancestors has no external parents.
+    assert_eq!(external("A B"), "");
+}
+
+#[test]
+fn test_ancestors_of_all() {
+    // B and C fork from A; D continues B.
+    let ascii = r#"
+        D
+        |
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let of_all = |set: &str| expand(r(dag.ancestors_of_all(nameset(set))).unwrap());
+
+    // `ancestors` unions ancestors of any member; `ancestors_of_all`
+    // intersects them.
+    assert_eq!(expand(r(dag.ancestors(nameset("B C"))).unwrap()), "A B C");
+    assert_eq!(of_all("B C"), "A");
+    // `ancestors` is inclusive, so a member that is an ancestor of every
+    // other member is part of the result, matching `common_ancestors`.
+    assert_eq!(of_all("B D"), "A B");
+    assert_eq!(
+        expand(r(dag.common_ancestors(nameset("B D"))).unwrap()),
+        "A B"
+    );
+    // A single vertex is an ancestor of itself.
+    assert_eq!(of_all("C"), "A C");
+    assert_eq!(of_all(""), "");
+}
+
+#[test]
+fn test_to_parents_map() {
+    // D is a merge of B and C.
+    let ascii = r#"
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+
+    // Round trip: export the full graph, re-import via add_heads through
+    // the `Parents` impl for HashMap, and compare parent_names.
+    let map = r(dag.to_parents_map(r(dag.all()).unwrap(), true)).unwrap();
+    let mut imported = MemNameDag::new();
+    r(imported.add_heads(&map, &vec![VertexName::from("D")].into())).unwrap();
+    for name in ["A", "B", "C", "D"] {
+        assert_eq!(
+            r(imported.parent_names(name.into())).unwrap(),
+            r(dag.parent_names(name.into())).unwrap(),
+        );
+    }
+
+    // Restricted export: parents outside the set are omitted, so the map
+    // is closed under the set. B's parent A disappears; D keeps only B.
+    let map = r(dag.to_parents_map(nameset("B D"), false)).unwrap();
+    assert_eq!(map.len(), 2);
+    assert!(map[&VertexName::from("B")].is_empty());
+    assert_eq!(map[&VertexName::from("D")], vec![VertexName::from("B")]);
+
+    // With include_external, external parents are kept as-is.
+    let map = r(dag.to_parents_map(nameset("D"), true)).unwrap();
+    assert_eq!(
+        map[&VertexName::from("D")],
+        vec![VertexName::from("B"), VertexName::from("C")],
+    );
+}
+
+#[test]
+fn test_max_depth() {
+    // E merges a 3-edge branch (A-B-D-E) with a 1-edge branch (C-E).
+    let ascii = r#"
+        E
+        |\
+        D C
+        |
+        B
+        |
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let depth = |set: &str| r(dag.max_depth(nameset(set))).unwrap();
+
+    // The deeper branch determines the depth.
+    assert_eq!(depth("E"), 3);
+    assert_eq!(depth("D"), 2);
+    // Roots have depth 0.
+    assert_eq!(depth("C"), 0);
+    assert_eq!(depth("A C"), 0);
+    // The maximum is taken across the set.
+    assert_eq!(depth("D C"), 2);
+    assert_eq!(r(dag.max_depth(r(dag.all()).unwrap())).unwrap(), 3);
+    assert_eq!(depth(""), 0);
+}
+
+#[test]
+fn test_parent_names_batch() {
+    // D is a merge of B and C.
+    let ascii = r#"
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let v = |name: &str| VertexName::copy_from(name.as_bytes());
+
+    let names = vec![v("D"), v("A"), v("C")];
+    let batch = r(dag.parent_names_batch(names.clone())).unwrap();
+
+    // One entry per input vertex, in input order.
+    assert_eq!(
+        batch.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>(),
+        names
+    );
+    // Each entry preserves the parent order reported by `parent_names`,
+    // including the first/second parent distinction of the merge D.
+    for (name, parents) in &batch {
+        assert_eq!(parents, &r(dag.parent_names(name.clone())).unwrap());
+    }
+    assert_eq!(batch[0].1.len(), 2);
+    assert!(batch[1].1.is_empty());
+}
+
+#[test]
+fn test_prefetch() {
+    // The in-memory dag uses the default no-op prefetch.
+    let dag = from_ascii(MemNameDag::new(), "A-B-C-D");
+    r(dag.prefetch(r(dag.ancestors(nameset("D"))).unwrap())).unwrap();
+    assert!(r(dag.is_ancestor("A".into(), "D".into())).unwrap());
+
+    // The segmented dag warms its id map. Correctness is unchanged; the
+    // benefit (fewer lookups during the queries below) is backend-specific.
+    let dir = tempdir().unwrap();
+    let mut dag = NameDag::open(dir.path()).unwrap();
+    dag = from_ascii(dag, "A-B-C-D");
+    r(dag.flush(&Default::default())).unwrap();
+    r(dag.prefetch(r(dag.ancestors(nameset("D"))).unwrap())).unwrap();
+    assert!(r(dag.is_ancestor("B".into(), "D".into())).unwrap());
+    assert!(!r(dag.is_ancestor("D".into(), "B".into())).unwrap());
+
+    // Prefetching an empty set is a no-op.
+    r(dag.prefetch(nameset(""))).unwrap();
+}
+
+#[test]
+fn test_to_id_set_spans() {
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D---E");
+    let streamed = |set: &crate::NameSet| -> Vec<(Id, Id)> {
+        let mut spans = Vec::new();
+        r(dag.to_id_set_spans(set, &mut |low, high| spans.push((low, high)))).unwrap();
+        spans
+    };
+    let materialized = |set: &crate::NameSet| -> Vec<(Id, Id)> {
+        r(dag.to_id_set(set))
+            .unwrap()
+            .iter_span_desc()
+            .map(|s| (s.low, s.high))
+            .collect()
+    };
+
+    // Id-backed set (fast path): streamed spans match the materialized set.
+    let all = r(dag.all()).unwrap();
+    assert_eq!(streamed(&all), materialized(&all));
+    assert!(!streamed(&all).is_empty());
+
+    // Non-contiguous id-backed set produces multiple spans.
+    let sparse = r(dag.sort(&nameset("A B D E"))).unwrap();
+    assert_eq!(streamed(&sparse), materialized(&sparse));
+    assert!(streamed(&sparse).len() >= 2);
+
+    // Plain static set (slow path) agrees too.
+    let named = nameset("B C");
+    assert_eq!(streamed(&named), materialized(&named));
+}
+
+#[test]
+fn test_id_map_compatibility() {
+    let dag1 = from_ascii(MemNameDag::new(), "A---B");
+    let dag2 = from_ascii(MemNameDag::new(), "A---B");
+    // A map is compatible with itself but not with a different map, even
+    // if the graphs have identical shapes.
+    assert!(dag1.is_compatible_with(&dag1));
+    assert!(!dag1.is_compatible_with(&dag2));
+    assert!(!dag2.is_compatible_with(&dag1));
+}
+
+#[test]
+fn test_branch_points() {
+    // History diverges at B into C and D.
+    let ascii = r#"
+        C D
+        |/
+        B
+        |
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    assert_eq!(
+        expand(r(dag.branch_points(nameset("A B C D"))).unwrap()),
+        "B"
+    );
+    // The fork point is only reported if it is in the input set.
+    assert_eq!(expand(r(dag.branch_points(nameset("A C D"))).unwrap()), "");
+}
+
+#[test]
+fn test_range_inclusive_roots() {
+    // Two disconnected components: A---B and X---Y.
+    let dag = from_ascii(from_ascii(MemNameDag::new(), "A---B"), "X---Y");
+    assert_eq!(
+        expand(r(dag.range(nameset("A X"), nameset("B"))).unwrap()),
+        "A B"
+    );
+    // The disconnected root X is forcibly included.
+    assert_eq!(
+        expand(r(dag.range_inclusive_roots(nameset("A X"), nameset("B"))).unwrap()),
+        "A B X"
+    );
+}
+
+#[test]
+fn test_ancestors_excluding() {
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D");
+    assert_eq!(
+        expand(r(dag.ancestors_excluding(nameset("D"), nameset("B"))).unwrap()),
+        "C D"
+    );
+    // The head stays present even when it is an ancestor of the exclude
+    // set, unlike `only` which would drop it.
+    assert_eq!(
+        expand(r(dag.ancestors_excluding(nameset("B"), nameset("D"))).unwrap()),
+        "B"
+    );
+    assert_eq!(
+        expand(r(dag.only(nameset("B"), nameset("D"))).unwrap()),
+        ""
+    );
+}
+
+#[test]
+fn test_reachable_heads_map() {
+    // Fork at B: C leads to head E, D leads to head F.
+    let ascii = r#"
+        E F
+        | |
+        C D
+        |/
+        B
+        |
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let v = |name: &str| -> VertexName { VertexName::copy_from(name.as_bytes()) };
+
+    let map = r(dag.reachable_heads_map(nameset("A C D"), nameset("E F"))).unwrap();
+    assert_eq!(expand(map[&v("A")].clone()), "E F");
+    assert_eq!(expand(map[&v("C")].clone()), "E");
+    assert_eq!(expand(map[&v("D")].clone()), "F");
+
+    // A root reaching no head maps to an empty set.
+    let map = r(dag.reachable_heads_map(nameset("E"), nameset("F"))).unwrap();
+    assert_eq!(expand(map[&v("E")].clone()), "");
+}
+
+#[test]
+fn test_is_ancestor_batch() {
+    // Two branches (B, C) reconverge at the merge D.
+    let ascii = r#"
+        D
+        |\
+        B C
+        |/
+        A"#;
+    let dag = from_ascii(MemNameDag::new(), ascii);
+    let pairs: Vec<(VertexName, VertexName)> = vec![
+        ("A".into(), "D".into()), // ancestor
+        ("B".into(), "C".into()), // siblings
+        ("C".into(), "C".into()), // equal vertexes
+        ("D".into(), "A".into()), // reversed
+    ];
+    assert_eq!(
+        r(dag.is_ancestor_batch(pairs)).unwrap(),
+        [true, false, true, false]
+    );
+
+    // A pair containing an unknown vertex errors out instead of
+    // reporting false.
+    assert!(r(dag.is_ancestor_batch(vec![("A".into(), "Z".into())])).is_err());
+}
+
+#[test]
+fn test_ancestors_within() {
+    let dag = from_ascii(MemNameDag::new(), "A---B---C---D---E---F");
+
+    // Depth 0 is just the input set.
+    assert_eq!(expand(r(dag.ancestors_within(nameset("F"), 0)).unwrap()), "F");
+    // From a single head, depth N covers N + 1 vertexes on a linear chain.
+    assert_eq!(
+        expand(r(dag.ancestors_within(nameset("F"), 2)).unwrap()),
+        "D E F"
+    );
+    // Depth beyond the root is capped at the full ancestor set.
+    assert_eq!(
+        expand(r(dag.ancestors_within(nameset("F"), 100)).unwrap()),
+        "A B C D E F"
+    );
+}
+
+#[test]
+fn test_contains_all_any() {
+    let dag = from_ascii(MemNameDag::new(), ASCII_DAG1);
+
+    // Subset.
+    assert!(r(dag.contains_all(nameset("A B"), nameset("A B C"))).unwrap());
+    assert!(r(dag.contains_any(nameset("A B"), nameset("A B C"))).unwrap());
+
+    // Disjoint.
+    assert!(!r(dag.contains_all(nameset("D E"), nameset("A B C"))).unwrap());
+    assert!(!r(dag.contains_any(nameset("D E"), nameset("A B C"))).unwrap());
+
+    // Partial overlap.
+    assert!(!r(dag.contains_all(nameset("C D"), nameset("A B C"))).unwrap());
+    assert!(r(dag.contains_any(nameset("C D"), nameset("A B C"))).unwrap());
+
+    // Empty needles.
+    assert!(r(dag.contains_all(nameset(""), nameset("A B C"))).unwrap());
+    assert!(!r(dag.contains_any(nameset(""), nameset("A B C"))).unwrap());
+}
+
 #[test]
 fn test_mem_namedag() {
     let new_dag = MemNameDag::new;
@@ -782,6 +1519,35 @@ fn test_namedag_reassign_master() -> crate::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_namedag_flush_conflict() {
+    let dir = tempdir().unwrap();
+
+    // Seed the on-disk graph.
+    let mut dag1 = NameDag::open(dir.path()).unwrap();
+    dag1 = from_ascii(dag1, "A-B");
+    r(dag1.flush(&Default::default())).unwrap();
+
+    // A second writer sharing the path flushes first.
+    let mut dag2 = NameDag::open(dir.path()).unwrap();
+    dag2 = from_ascii(dag2, "B-C");
+    r(dag2.flush(&Default::default())).unwrap();
+
+    // dag1's view is now stale. Its flush reports a typed conflict, not a
+    // generic or IO error.
+    let mut dag1 = from_ascii(dag1, "B-D");
+    let err = r(dag1.flush(&Default::default())).unwrap_err();
+    assert!(matches!(err, crate::errors::DagError::FlushConflict(_)));
+
+    // Retrying after reopening and re-deriving heads succeeds and sees both
+    // writers' vertexes.
+    let dag3 = NameDag::open(dir.path()).unwrap();
+    let mut dag3 = from_ascii(dag3, "B-D");
+    r(dag3.flush(&Default::default())).unwrap();
+    assert!(r(r(dag3.all()).unwrap().contains(&"C".into())).unwrap());
+    assert!(r(r(dag3.all()).unwrap().contains(&"D".into())).unwrap());
+}
+
 #[test]
 fn test_namedag_reassign_non_master() {
     let mut t = TestDag::new();
@@ -817,6 +1583,35 @@ fn test_namedag_reassign_non_master() {
     assert_eq!(dbg(z_vertex), "Z");
 }
 
+#[test]
+fn test_vertex_names_bulk() {
+    let mut t = TestDag::new();
+    t.drawdag("A--B--C", &[]);
+
+    let a = r(t.dag.vertex_id("A".into())).unwrap();
+    let b = r(t.dag.vertex_id("B".into())).unwrap();
+    let c = r(t.dag.vertex_id("C".into())).unwrap();
+
+    // Output order follows input order.
+    let names = r(t.dag.vertex_names(vec![c, a, b])).unwrap();
+    assert_eq!(dbg(names), "[C, A, B]");
+
+    // An unknown id fails the whole call, like `vertex_name` would.
+    assert!(r(t.dag.vertex_names(vec![a, Id(1000)])).is_err());
+}
+
+#[test]
+fn test_contains_vertex_id() {
+    let mut t = TestDag::new();
+    t.drawdag("A--B--C", &[]);
+
+    let a = r(t.dag.vertex_id("A".into())).unwrap();
+    assert!(r(t.dag.contains_vertex_id(a)).unwrap());
+
+    // An id outside the mapped range is reported as absent, not an error.
+    assert!(!r(t.dag.contains_vertex_id(Id(1000))).unwrap());
+}
+
 #[test]
 fn test_segment_ancestors_example1() {
     // DAG from segmented-changelog.pdf
@@ -0,0 +1,1685 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Checksum table for a plain, append-only file.
+//!
+//! The table splits the source file into fixed-size chunks (except for the
+//! last chunk, which could be shorter) and records the xxhash of every chunk
+//! in a separate `.sum` file. [`ChecksumTable::check_range`] lazily verifies
+//! chunks covering a byte range and caches which chunks have already been
+//! verified, so repeated reads do not re-hash the same data.
+//!
+//! The checksum file format is:
+//!
+//! ```plain,ignore
+//! vlq(chunk_size_logarithm) vlq(covered_length) u64le(chunk_checksum)^n u64le(table_checksum)
+//! ```
+//!
+//! where the trailing `table_checksum` is the xxhash of all preceding bytes,
+//! protecting the table itself against corruption.
+//!
+//! When hole ranges are declared (see [`ChecksumTable::mark_holes`]), the
+//! file instead starts with a version marker `vlq(64 + version)`, which is
+//! unambiguous since `chunk_size_logarithm` is always below 64, and carries
+//! the hole list between the header and the checksums:
+//!
+//! ```plain,ignore
+//! vlq(65) vlq(chunk_size_logarithm) vlq(covered_length)
+//! vlq(hole_count) (vlq(hole_start) vlq(hole_length))^hole_count
+//! u64le(chunk_checksum)^n u64le(table_checksum)
+//! ```
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use byteorder::ByteOrder;
+use byteorder::LittleEndian;
+use byteorder::WriteBytesExt;
+use minibytes::Bytes;
+use vlqencoding::VLQDecode;
+use vlqencoding::VLQEncode;
+
+use crate::utils::atomic_write_plain;
+use crate::utils::mmap_bytes;
+use crate::utils::xxhash;
+
+/// Default chunk size: 1MB.
+const DEFAULT_CHUNK_SIZE_LOGARITHM: u32 = 20;
+
+/// First-vlq marker distinguishing versioned checksum files from the legacy
+/// format, whose first vlq (the chunk size logarithm) is always below 64.
+/// The format version is the marker minus this base.
+const FORMAT_MARKER_BASE: u64 = 64;
+
+/// Format version that adds the hole list. Only written when holes are
+/// declared, so hole-free tables keep producing legacy files.
+const FORMAT_VERSION_HOLES: u64 = 1;
+
+/// Error type returned by [`ChecksumTable`] methods. Unlike the crate-wide
+/// opaque [`crate::Error`], the variants are matchable, so callers can tell
+/// data corruption apart from plain IO failures deterministically.
+#[derive(Debug, thiserror::Error)]
+pub enum ChecksumTableError {
+    /// The source file or the checksum file does not match the recorded
+    /// checksums.
+    #[error("checksum table corruption: {0}")]
+    Corruption(String),
+
+    /// A requested range or length is outside what the checksum table
+    /// covers, or the source file is shorter than the table expects.
+    #[error("checksum table range error: {0}")]
+    Truncated(String),
+
+    /// The chunk geometry (chunk size logarithm or checksum count) is
+    /// invalid.
+    #[error("invalid checksum table chunk size: {0}")]
+    InvalidChunkSize(String),
+
+    /// A mutating method was called on a table opened via
+    /// [`ChecksumTable::open_readonly`].
+    #[error("checksum table is read-only: {0}")]
+    ReadOnly(String),
+
+    /// An IO error reading or writing the source or checksum file.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Convert into the crate-wide opaque [`crate::Error`] for callers that mix
+/// [`ChecksumTable`] with the rest of the crate. [`ChecksumTableError::Corruption`]
+/// keeps its corruption marker across the conversion, so
+/// [`crate::Error::is_corruption`] keeps working.
+impl From<ChecksumTableError> for crate::Error {
+    fn from(err: ChecksumTableError) -> Self {
+        let is_corruption = matches!(err, ChecksumTableError::Corruption(_));
+        let result = crate::Error::blank()
+            .message("checksum table error")
+            .source(err);
+        if is_corruption {
+            result.mark_corruption()
+        } else {
+            result
+        }
+    }
+}
+
+/// Append `.ext` to a path.
+fn path_appendext(path: &Path, ext: &str) -> PathBuf {
+    let mut buf = path.to_path_buf().into_os_string();
+    buf.push(".");
+    buf.push(ext);
+    buf.into()
+}
+
+/// Checksum table for a source file.
+pub struct ChecksumTable {
+    // Source file.
+    path: PathBuf,
+    file: fs::File,
+    buf: Bytes,
+
+    // Checksum file.
+    checksum_path: PathBuf,
+    fsync: bool,
+    defer_fsync: bool,
+    // Set when a deferred fsync is owed; cleared by `sync`.
+    needs_fsync: Cell<bool>,
+
+    // Parsed checksum data.
+    chunk_size_log: u32,
+    end: u64,
+    checksums: Checksums,
+    // Declared hole ranges `(start, length)`, sorted and non-overlapping.
+    // Chunks entirely inside a hole are never hashed. See `mark_holes`.
+    holes: Vec<(u64, u64)>,
+
+    // Whether `update` verifies the entire existing range first.
+    verify_on_update: bool,
+
+    // Whether mutating methods are rejected. See `open_readonly`.
+    readonly: bool,
+
+    // Bit vector of lazily verified chunks.
+    checked: RefCell<Vec<u64>>,
+}
+
+/// Parsed header fields of a checksum file, without the checksum list.
+/// See [`ChecksumTable::header_only`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Logarithm of the chunk size. Chunk size is `1 << chunk_size_log`.
+    pub chunk_size_log: u32,
+    /// Length of the source file covered by the table, in bytes.
+    pub end: u64,
+    /// Number of chunk checksums recorded in the table.
+    pub chunk_count: usize,
+}
+
+/// Storage for the per-chunk checksum list. Either an owned `Vec` (the
+/// default), or a mapping of the `.sum` file with checksums decoded on
+/// demand, so very large tables do not duplicate the file in memory. The
+/// table checksum is validated once at open time in both cases.
+#[derive(Clone)]
+enum Checksums {
+    Owned(Vec<u64>),
+    Mapped {
+        bytes: Bytes,
+        /// Byte offset of the first checksum within `bytes`.
+        offset: usize,
+        count: usize,
+    },
+}
+
+impl Checksums {
+    fn len(&self) -> usize {
+        match self {
+            Checksums::Owned(checksums) => checksums.len(),
+            Checksums::Mapped { count, .. } => *count,
+        }
+    }
+
+    fn get(&self, index: usize) -> u64 {
+        match self {
+            Checksums::Owned(checksums) => checksums[index],
+            Checksums::Mapped { bytes, offset, .. } => {
+                LittleEndian::read_u64(&bytes[offset + index * 8..])
+            }
+        }
+    }
+
+    /// Copy the first `count` checksums into an owned `Vec`.
+    fn prefix(&self, count: usize) -> Vec<u64> {
+        (0..count).map(|index| self.get(index)).collect()
+    }
+}
+
+/// A chunk that failed checksum verification, reported by
+/// [`ChecksumTable::iter_failed_chunks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailedChunk {
+    /// Index of the chunk in the table.
+    pub index: usize,
+    /// Start of the chunk's byte range in the source file (inclusive).
+    pub start: u64,
+    /// End of the chunk's byte range in the source file (exclusive).
+    pub end: u64,
+}
+
+/// Builder for [`ChecksumTable`], allowing the checksum file to live
+/// somewhere other than next to the source file.
+pub struct ChecksumTableBuilder {
+    path: PathBuf,
+    checksum_path: Option<PathBuf>,
+    fsync: bool,
+    defer_fsync: bool,
+    mmap_checksums: bool,
+}
+
+impl ChecksumTableBuilder {
+    /// Specify where the checksum file lives. The default is the source
+    /// path with `.sum` appended. This is useful when the source lives on
+    /// a read-only mount.
+    pub fn checksum_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.checksum_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set whether checksum file writes are fsync-ed. Default: false.
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Defer the fsync issued by [`ChecksumTable::update`] until
+    /// [`ChecksumTable::sync`] is called, so a batch of N updates pays one
+    /// durability barrier instead of N. Each update still replaces the
+    /// checksum file atomically; only the barrier is batched. Only
+    /// meaningful together with `fsync(true)`. Default: false.
+    pub fn defer_fsync(mut self, enabled: bool) -> Self {
+        self.defer_fsync = enabled;
+        self
+    }
+
+    /// Memory-map the checksum file and decode checksums on demand,
+    /// instead of copying them into an owned `Vec`. Saves memory for very
+    /// large tables. Default: false.
+    pub fn mmap_checksums(mut self, enabled: bool) -> Self {
+        self.mmap_checksums = enabled;
+        self
+    }
+
+    /// Open the source file and load the checksum table.
+    ///
+    /// A missing checksum file is not an error - the table starts empty and
+    /// covers nothing until [`ChecksumTable::update`] is called.
+    pub fn open(self) -> Result<ChecksumTable, ChecksumTableError> {
+        let file = OpenOptions::new().read(true).open(&self.path)?;
+        let buf = mmap_bytes(&file, None)?;
+        let checksum_path = self
+            .checksum_path
+            .unwrap_or_else(|| path_appendext(&self.path, "sum"));
+
+        let (chunk_size_log, end, checksums, holes) = if self.mmap_checksums {
+            match fs::File::open(&checksum_path) {
+                Ok(sum_file) => {
+                    let bytes = mmap_bytes(&sum_file, None)?;
+                    let (chunk_size_log, end, offset, count, holes) =
+                        validate_checksum_file(&bytes)?;
+                    (
+                        chunk_size_log,
+                        end,
+                        Checksums::Mapped {
+                            bytes,
+                            offset,
+                            count,
+                        },
+                        holes,
+                    )
+                }
+                Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => (
+                    DEFAULT_CHUNK_SIZE_LOGARITHM,
+                    0,
+                    Checksums::Owned(Vec::new()),
+                    Vec::new(),
+                ),
+                Err(err) => return Err(err.into()),
+            }
+        } else {
+            match fs::read(&checksum_path) {
+                Ok(content) => {
+                    let (chunk_size_log, end, checksums, holes) = parse_checksum_file(&content)?;
+                    (chunk_size_log, end, Checksums::Owned(checksums), holes)
+                }
+                Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => (
+                    DEFAULT_CHUNK_SIZE_LOGARITHM,
+                    0,
+                    Checksums::Owned(Vec::new()),
+                    Vec::new(),
+                ),
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        let checked = RefCell::new(vec![0u64; checksums.len().div_ceil(64)]);
+        Ok(ChecksumTable {
+            path: self.path,
+            file,
+            buf,
+            checksum_path,
+            fsync: self.fsync,
+            defer_fsync: self.defer_fsync,
+            needs_fsync: Cell::new(false),
+            chunk_size_log,
+            end,
+            checksums,
+            holes,
+            verify_on_update: false,
+            readonly: false,
+            checked,
+        })
+    }
+}
+
+/// Validate a checksum file (length and trailing table checksum), returning
+/// `(chunk_size_log, end, checksum_offset, chunk_count, holes)` without
+/// decoding the individual chunk checksums.
+fn validate_checksum_file(
+    content: &[u8],
+) -> Result<(u32, u64, usize, usize, Vec<(u64, u64)>), ChecksumTableError> {
+    let mut cur = Cursor::new(content);
+    let first: u64 = cur.read_vlq()?;
+    let (version, chunk_size_log) = if first >= FORMAT_MARKER_BASE {
+        let version = first - FORMAT_MARKER_BASE;
+        if version != FORMAT_VERSION_HOLES {
+            return Err(ChecksumTableError::Corruption(format!(
+                "unsupported checksum file version: {}",
+                version
+            )));
+        }
+        (version, cur.read_vlq()?)
+    } else {
+        (0, first as u32)
+    };
+    if chunk_size_log >= 64 {
+        return Err(ChecksumTableError::InvalidChunkSize(format!(
+            "invalid chunk size logarithm: {}",
+            chunk_size_log
+        )));
+    }
+    let end: u64 = cur.read_vlq()?;
+    let mut holes = Vec::new();
+    if version == FORMAT_VERSION_HOLES {
+        let hole_count: usize = cur.read_vlq()?;
+        for _ in 0..hole_count {
+            let start: u64 = cur.read_vlq()?;
+            let length: u64 = cur.read_vlq()?;
+            holes.push((start, length));
+        }
+    }
+    let chunk_size = 1u64 << chunk_size_log;
+    let count = (end.div_ceil(chunk_size)) as usize;
+    let pos = cur.position() as usize;
+    let expected_len = pos + (count + 1) * 8;
+    if content.len() != expected_len {
+        return Err(ChecksumTableError::Corruption(format!(
+            "checksum file has unexpected length: {} (expected {})",
+            content.len(),
+            expected_len
+        )));
+    }
+    let table_checksum = LittleEndian::read_u64(&content[pos + count * 8..]);
+    if xxhash(&content[..pos + count * 8]) != table_checksum {
+        return Err(ChecksumTableError::Corruption(
+            "checksum file is corrupt".to_string(),
+        ));
+    }
+    Ok((chunk_size_log, end, pos, count, holes))
+}
+
+/// Serialize table contents into the checksum file format, including the
+/// trailing table checksum. A non-empty hole list selects the versioned
+/// format; without holes the legacy format is written.
+fn serialize_checksum_file(
+    chunk_size_log: u32,
+    end: u64,
+    checksums: &[u64],
+    holes: &[(u64, u64)],
+) -> Result<Vec<u8>, ChecksumTableError> {
+    let mut content = Vec::with_capacity(16 + holes.len() * 4 + checksums.len() * 8);
+    if !holes.is_empty() {
+        content.write_vlq(FORMAT_MARKER_BASE + FORMAT_VERSION_HOLES)?;
+    }
+    content.write_vlq(chunk_size_log)?;
+    content.write_vlq(end)?;
+    if !holes.is_empty() {
+        content.write_vlq(holes.len())?;
+        for &(start, length) in holes {
+            content.write_vlq(start)?;
+            content.write_vlq(length)?;
+        }
+    }
+    for checksum in checksums {
+        content.write_u64::<LittleEndian>(*checksum)?;
+    }
+    let table_checksum = xxhash(&content);
+    content.write_u64::<LittleEndian>(table_checksum)?;
+    Ok(content)
+}
+
+type ParsedChecksumFile = (u32, u64, Vec<u64>, Vec<(u64, u64)>);
+
+fn parse_checksum_file(content: &[u8]) -> Result<ParsedChecksumFile, ChecksumTableError> {
+    let (chunk_size_log, end, pos, count, holes) = validate_checksum_file(content)?;
+    let mut checksums = Vec::with_capacity(count);
+    for i in 0..count {
+        checksums.push(LittleEndian::read_u64(&content[pos + i * 8..]));
+    }
+    Ok((chunk_size_log, end, checksums, holes))
+}
+
+/// Test if `start..end` lies entirely within one declared hole.
+fn range_in_holes(holes: &[(u64, u64)], start: u64, end: u64) -> bool {
+    holes
+        .iter()
+        .any(|&(hole_start, hole_len)| start >= hole_start && end <= hole_start + hole_len)
+}
+
+impl ChecksumTable {
+    /// Open the checksum table for `path`. The checksum file is `path` with
+    /// `.sum` appended.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, ChecksumTableError> {
+        Self::builder(path).open()
+    }
+
+    /// Return a [`ChecksumTableBuilder`] for customizing how the table is
+    /// opened.
+    pub fn builder(path: impl AsRef<Path>) -> ChecksumTableBuilder {
+        ChecksumTableBuilder {
+            path: path.as_ref().to_path_buf(),
+            checksum_path: None,
+            fsync: false,
+            defer_fsync: false,
+            mmap_checksums: false,
+        }
+    }
+
+    /// Open the table for reading only. The source and checksum files are
+    /// opened read-only and no write syscalls are ever issued: `update`,
+    /// `update_repair`, `truncate_to` and `clear` return
+    /// [`ChecksumTableError::ReadOnly`]. Intended for strictly read-only
+    /// media. `checksum_path` defaults to `source_path` with `.sum`
+    /// appended; a missing checksum file yields a table covering nothing.
+    pub fn open_readonly(
+        source_path: impl AsRef<Path>,
+        checksum_path: Option<&Path>,
+    ) -> Result<Self, ChecksumTableError> {
+        let mut builder = Self::builder(source_path);
+        if let Some(path) = checksum_path {
+            builder = builder.checksum_path(path);
+        }
+        let mut table = builder.open()?;
+        table.readonly = true;
+        Ok(table)
+    }
+
+    /// Reject mutating calls on a read-only table.
+    fn check_writable(&self, operation: &str) -> Result<(), ChecksumTableError> {
+        if self.readonly {
+            return Err(ChecksumTableError::ReadOnly(format!(
+                "cannot {} a read-only checksum table",
+                operation
+            )));
+        }
+        Ok(())
+    }
+
+    /// Describe the table's metadata in a human-readable form for
+    /// debugging. Does not verify any chunk.
+    pub fn describe(&self) -> String {
+        format!(
+            "checksum table for {} (checksum file: {}):\n  \
+             chunk size: {} (log: {})\n  covered bytes: {}\n  chunks: {}\n  \
+             holes: {}",
+            self.path.display(),
+            self.checksum_path.display(),
+            1u64 << self.chunk_size_log,
+            self.chunk_size_log,
+            self.end,
+            self.checksums.len(),
+            self.holes.len(),
+        )
+    }
+
+    /// Length of the source file covered by the table, in bytes. The table
+    /// lags behind an append-only source while appends accumulate; see
+    /// [`ChecksumTable::source_len`] to detect staleness.
+    pub fn covered_len(&self) -> u64 {
+        self.end
+    }
+
+    /// Current length of the source file on disk, in bytes. When this
+    /// exceeds [`ChecksumTable::covered_len`], the file grew and the table
+    /// is stale until [`ChecksumTable::update`] is called.
+    pub fn source_len(&self) -> Result<u64, ChecksumTableError> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// Parse only the header fields of the checksum file at `path` (the
+    /// `.sum` path itself), without loading the checksum list or mmapping
+    /// the source file. Does not verify the table checksum.
+    pub fn header_only(path: impl AsRef<Path>) -> Result<Header, ChecksumTableError> {
+        // The header is two vlq values, at most 15 bytes in total.
+        let mut buf = Vec::with_capacity(24);
+        fs::File::open(path.as_ref())?
+            .take(24)
+            .read_to_end(&mut buf)?;
+        let mut cur = Cursor::new(&buf[..]);
+        let first: u64 = cur.read_vlq()?;
+        let chunk_size_log: u32 = if first >= FORMAT_MARKER_BASE {
+            if first - FORMAT_MARKER_BASE != FORMAT_VERSION_HOLES {
+                return Err(ChecksumTableError::Corruption(format!(
+                    "unsupported checksum file version: {}",
+                    first - FORMAT_MARKER_BASE
+                )));
+            }
+            cur.read_vlq()?
+        } else {
+            first as u32
+        };
+        if chunk_size_log >= 64 {
+            return Err(ChecksumTableError::InvalidChunkSize(format!(
+                "invalid chunk size logarithm: {}",
+                chunk_size_log
+            )));
+        }
+        let end: u64 = cur.read_vlq()?;
+        let chunk_size = 1u64 << chunk_size_log;
+        Ok(Header {
+            chunk_size_log,
+            end,
+            chunk_count: end.div_ceil(chunk_size) as usize,
+        })
+    }
+
+    /// Set whether [`ChecksumTable::update`] verifies the entire existing
+    /// range before appending, so corruption anywhere in the file is caught
+    /// at write time instead of at the next read. Default: false.
+    pub fn verify_on_update(mut self, enabled: bool) -> Self {
+        self.verify_on_update = enabled;
+        self
+    }
+
+    /// Verify the given byte range against the recorded checksums.
+    ///
+    /// Returns an error if the range is not covered by the table, or if any
+    /// chunk covering the range fails its checksum.
+    pub fn check_range(&self, offset: u64, length: u64) -> Result<(), ChecksumTableError> {
+        if length == 0 {
+            return Ok(());
+        }
+        if offset + length > self.end {
+            return Err(ChecksumTableError::Truncated(format!(
+                "range {}..{} is not covered by the checksum table (covered: {})",
+                offset,
+                offset + length,
+                self.end
+            )));
+        }
+        let start = (offset >> self.chunk_size_log) as usize;
+        let end = ((offset + length - 1) >> self.chunk_size_log) as usize;
+        for index in start..=end {
+            if !self.check_chunk(index) {
+                return Err(ChecksumTableError::Corruption(format!(
+                    "chunk {} (starting at byte {}) failed checksum verification",
+                    index,
+                    (index as u64) << self.chunk_size_log,
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify a caller-supplied copy of the source bytes against the stored
+    /// checksums, instead of the table's own mapping. Useful when the source
+    /// of truth is a slice of a larger mmap the caller already holds, to
+    /// avoid a second mapping.
+    ///
+    /// `data` must contain at least the bytes covered by the table, starting
+    /// from offset 0 of the source file. Unlike [`ChecksumTable::check_range`],
+    /// this neither consults nor updates the verified-chunk cache, since the
+    /// cache only describes the table's own mapping.
+    pub fn check_range_against(
+        &self,
+        data: &[u8],
+        offset: u64,
+        length: u64,
+    ) -> Result<(), ChecksumTableError> {
+        if (data.len() as u64) < self.end {
+            return Err(ChecksumTableError::Truncated(format!(
+                "external buffer is too short: {} bytes (checksum table covers {})",
+                data.len(),
+                self.end
+            )));
+        }
+        if length == 0 {
+            return Ok(());
+        }
+        if offset + length > self.end {
+            return Err(ChecksumTableError::Truncated(format!(
+                "range {}..{} is not covered by the checksum table (covered: {})",
+                offset,
+                offset + length,
+                self.end
+            )));
+        }
+        let start = (offset >> self.chunk_size_log) as usize;
+        let end = ((offset + length - 1) >> self.chunk_size_log) as usize;
+        for index in start..=end {
+            let chunk_start = (index as u64) << self.chunk_size_log;
+            let chunk_end = (chunk_start + (1 << self.chunk_size_log)).min(self.end);
+            if range_in_holes(&self.holes, chunk_start, chunk_end) {
+                continue;
+            }
+            if xxhash(&data[chunk_start as usize..chunk_end as usize]) != self.checksums.get(index) {
+                return Err(ChecksumTableError::Corruption(format!(
+                    "chunk {} (starting at byte {}) failed checksum verification",
+                    index,
+                    chunk_start,
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify multiple byte ranges in one call.
+    ///
+    /// The chunks covering the ranges are deduplicated first, so a chunk
+    /// touched by several (possibly overlapping) ranges is hashed at most
+    /// once. Returns the first failure, if any.
+    pub fn check_ranges(&self, ranges: &[(u64, u64)]) -> Result<(), ChecksumTableError> {
+        let mut chunks = std::collections::BTreeSet::new();
+        for &(offset, length) in ranges {
+            if length == 0 {
+                continue;
+            }
+            if offset + length > self.end {
+                return Err(ChecksumTableError::Truncated(format!(
+                    "range {}..{} is not covered by the checksum table (covered: {})",
+                    offset,
+                    offset + length,
+                    self.end
+                )));
+            }
+            let start = (offset >> self.chunk_size_log) as usize;
+            let end = ((offset + length - 1) >> self.chunk_size_log) as usize;
+            chunks.extend(start..=end);
+        }
+        for index in chunks {
+            if !self.check_chunk(index) {
+                return Err(ChecksumTableError::Corruption(format!(
+                    "chunk {} (starting at byte {}) failed checksum verification",
+                    index,
+                    (index as u64) << self.chunk_size_log,
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hash every covered chunk and yield the ones that do not match their
+    /// recorded checksums, instead of stopping at the first failure like
+    /// [`ChecksumTable::check_range`]. Intended for audits that want a full
+    /// damage report in one pass.
+    ///
+    /// This neither consults nor updates the verified-chunk cache: every
+    /// chunk is re-hashed, and failing chunks are not marked as checked.
+    pub fn iter_failed_chunks(&self) -> impl Iterator<Item = FailedChunk> + '_ {
+        let chunk_size = 1u64 << self.chunk_size_log;
+        (0..self.checksums.len()).filter_map(move |index| {
+            let start = (index as u64) << self.chunk_size_log;
+            let end = (start + chunk_size).min(self.end);
+            if range_in_holes(&self.holes, start, end) {
+                return None;
+            }
+            let matched = end > start
+                && end as usize <= self.buf.len()
+                && xxhash(&self.buf[start as usize..end as usize]) == self.checksums.get(index);
+            if matched {
+                None
+            } else {
+                Some(FailedChunk { index, start, end })
+            }
+        })
+    }
+
+    /// Verify a single chunk, consulting and updating the verified bit
+    /// vector.
+    fn check_chunk(&self, index: usize) -> bool {
+        let mut checked = self.checked.borrow_mut();
+        if (checked[index / 64] >> (index % 64)) & 1 == 1 {
+            return true;
+        }
+        let start = (index as u64) << self.chunk_size_log;
+        let logical_end = (start + (1 << self.chunk_size_log)).min(self.end);
+        // Chunks entirely inside a declared hole are always valid and never
+        // read from the source.
+        if range_in_holes(&self.holes, start, logical_end) {
+            checked[index / 64] |= 1 << (index % 64);
+            return true;
+        }
+        let end = logical_end.min(self.buf.len() as u64);
+        if end <= start {
+            return false;
+        }
+        if xxhash(&self.buf[start as usize..end as usize]) == self.checksums.get(index) {
+            checked[index / 64] |= 1 << (index % 64);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Bring the table up-to-date with the source file, assuming the source
+    /// is append-only. Checksums of chunks that were fully covered before are
+    /// reused without re-hashing.
+    ///
+    /// `chunk_size_log` changes the chunk size (to `2 ** chunk_size_log`),
+    /// which forces re-hashing the entire file. `None` keeps the current
+    /// chunk size.
+    pub fn update(&mut self, chunk_size_log: Option<u32>) -> Result<(), ChecksumTableError> {
+        self.check_writable("update")?;
+        if self.verify_on_update {
+            self.check_range(0, self.end)?;
+        }
+        let chunk_size_log = chunk_size_log.unwrap_or(self.chunk_size_log);
+        if chunk_size_log >= 64 {
+            return Err(ChecksumTableError::InvalidChunkSize(format!(
+                "invalid chunk size logarithm: {}",
+                chunk_size_log
+            )));
+        }
+        let buf = mmap_bytes(&self.file, None)?;
+        let new_end = buf.len() as u64;
+        if new_end < self.end {
+            return Err(ChecksumTableError::Truncated(format!(
+                "cannot update checksum table: source file shrank from {} to {} bytes",
+                self.end,
+                new_end
+            )));
+        }
+
+        let chunk_size = 1u64 << chunk_size_log;
+        let mut checksums = if chunk_size_log == self.chunk_size_log {
+            // Reuse checksums of chunks that were complete.
+            let full_chunks = (self.end >> chunk_size_log) as usize;
+            self.checksums.prefix(full_chunks.min(self.checksums.len()))
+        } else {
+            Vec::new()
+        };
+        let mut offset = (checksums.len() as u64) * chunk_size;
+        while offset < new_end {
+            let end = (offset + chunk_size).min(new_end);
+            if range_in_holes(&self.holes, offset, end) {
+                // Hole chunks get a placeholder checksum; they are never
+                // hashed or compared.
+                checksums.push(0);
+            } else {
+                checksums.push(xxhash(&buf[offset as usize..end as usize]));
+            }
+            offset = end;
+        }
+
+        self.write_checksum_file(chunk_size_log, new_end, &checksums)?;
+
+        // Previously verified chunks whose checksums were reused remain
+        // verified; everything else needs re-verification.
+        let mut checked = vec![0u64; checksums.len().div_ceil(64)];
+        if chunk_size_log == self.chunk_size_log {
+            let full_chunks = (self.end >> chunk_size_log) as usize;
+            let old_checked = self.checked.borrow();
+            for index in 0..full_chunks.min(self.checksums.len()) {
+                checked[index / 64] |= old_checked[index / 64] & (1 << (index % 64));
+            }
+        }
+
+        self.buf = buf;
+        self.end = new_end;
+        self.chunk_size_log = chunk_size_log;
+        self.checksums = Checksums::Owned(checksums);
+        self.checked = RefCell::new(checked);
+        Ok(())
+    }
+
+    /// Recover from detected corruption without discarding all trust, for
+    /// callers who have independently validated a prefix of the source
+    /// (e.g. against a replica). Bytes below `known_good_prefix` are
+    /// trusted: chunks fully inside the prefix start out verified. All
+    /// checksums are recomputed from the current file content, keeping the
+    /// chunk size, and the table is rewritten.
+    ///
+    /// Rejects a prefix beyond the current file length.
+    pub fn update_repair(&mut self, known_good_prefix: u64) -> Result<(), ChecksumTableError> {
+        self.check_writable("repair")?;
+        let buf = mmap_bytes(&self.file, None)?;
+        let new_end = buf.len() as u64;
+        if known_good_prefix > new_end {
+            return Err(ChecksumTableError::Truncated(format!(
+                "cannot repair checksum table: trusted prefix {} exceeds file length {}",
+                known_good_prefix,
+                new_end
+            )));
+        }
+
+        let chunk_size = 1u64 << self.chunk_size_log;
+        let mut checksums = Vec::with_capacity(new_end.div_ceil(chunk_size) as usize);
+        let mut offset = 0;
+        while offset < new_end {
+            let end = (offset + chunk_size).min(new_end);
+            if range_in_holes(&self.holes, offset, end) {
+                checksums.push(0);
+            } else {
+                checksums.push(xxhash(&buf[offset as usize..end as usize]));
+            }
+            offset = end;
+        }
+
+        self.write_checksum_file(self.chunk_size_log, new_end, &checksums)?;
+
+        // Chunks fully covered by the trusted prefix start out verified;
+        // everything else goes through check_range as usual.
+        let trusted_chunks = (known_good_prefix >> self.chunk_size_log) as usize;
+        let mut checked = vec![0u64; checksums.len().div_ceil(64)];
+        for index in 0..trusted_chunks {
+            checked[index / 64] |= 1 << (index % 64);
+        }
+
+        self.buf = buf;
+        self.end = new_end;
+        self.checksums = Checksums::Owned(checksums);
+        self.checked = RefCell::new(checked);
+        Ok(())
+    }
+
+    /// Shrink the table so it covers only the first `new_len` bytes of the
+    /// source, for workflows where the source is truncated and then regrown.
+    /// A subsequent [`ChecksumTable::update`] then only re-hashes the regrown
+    /// tail instead of the whole file.
+    ///
+    /// `new_len` must not exceed the currently covered length. If `new_len`
+    /// falls inside a chunk, the now-partial last chunk is re-hashed.
+    pub fn truncate_to(&mut self, new_len: u64) -> Result<(), ChecksumTableError> {
+        self.check_writable("truncate")?;
+        if new_len > self.end {
+            return Err(ChecksumTableError::Truncated(format!(
+                "cannot truncate checksum table to {}: only {} bytes are covered",
+                new_len,
+                self.end
+            )));
+        }
+        if new_len == self.end {
+            return Ok(());
+        }
+
+        let chunk_size = 1u64 << self.chunk_size_log;
+        let full_chunks = (new_len >> self.chunk_size_log) as usize;
+        let mut checksums = self.checksums.prefix(full_chunks);
+
+        // Surviving full chunks keep their verified bits; a partial last
+        // chunk is re-hashed below and needs re-verification.
+        let chunk_count = new_len.div_ceil(chunk_size) as usize;
+        let mut checked = vec![0u64; chunk_count.div_ceil(64)];
+        {
+            let old_checked = self.checked.borrow();
+            for index in 0..full_chunks {
+                checked[index / 64] |= old_checked[index / 64] & (1 << (index % 64));
+            }
+        }
+
+        if new_len & (chunk_size - 1) != 0 {
+            let start = (full_chunks as u64) << self.chunk_size_log;
+            if range_in_holes(&self.holes, start, new_len) {
+                checksums.push(0);
+            } else {
+                checksums.push(xxhash(&self.buf[start as usize..new_len as usize]));
+            }
+        }
+
+        self.write_checksum_file(self.chunk_size_log, new_len, &checksums)?;
+
+        self.end = new_len;
+        self.checksums = Checksums::Owned(checksums);
+        self.checked = RefCell::new(checked);
+        Ok(())
+    }
+
+    /// Declare byte ranges of the source file as holes: logically unused
+    /// regions that are never written, given as `(start, length)` pairs.
+    ///
+    /// Chunks that lie entirely within a hole are treated as always-valid:
+    /// [`ChecksumTable::update`] skips hashing them and
+    /// [`ChecksumTable::check_range`] passes over them without reading the
+    /// source. Chunks only partially covered by a hole are hashed as usual,
+    /// so holes smaller than the chunk size buy nothing.
+    ///
+    /// Replaces any previously declared holes and persists the new list in
+    /// the checksum file (using the versioned format extension, see the
+    /// module documentation). Empty ranges are dropped; overlapping or
+    /// adjacent ranges are merged.
+    pub fn mark_holes(&mut self, ranges: &[(u64, u64)]) -> Result<(), ChecksumTableError> {
+        self.check_writable("mark holes in")?;
+        let mut holes: Vec<(u64, u64)> = ranges
+            .iter()
+            .copied()
+            .filter(|&(_, length)| length > 0)
+            .collect();
+        holes.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(holes.len());
+        for (start, length) in holes {
+            match merged.last_mut() {
+                Some((last_start, last_len)) if start <= *last_start + *last_len => {
+                    *last_len = (*last_len).max(start + length - *last_start);
+                }
+                _ => merged.push((start, length)),
+            }
+        }
+        self.holes = merged;
+        let checksums = self.checksums.prefix(self.checksums.len());
+        self.write_checksum_file(self.chunk_size_log, self.end, &checksums)?;
+        Ok(())
+    }
+
+    /// Atomically rewrite the `.sum` file with the given table contents.
+    fn write_checksum_file(
+        &self,
+        chunk_size_log: u32,
+        end: u64,
+        checksums: &[u64],
+    ) -> Result<(), ChecksumTableError> {
+        let content = serialize_checksum_file(chunk_size_log, end, checksums, &self.holes)?;
+        atomic_write_plain(&self.checksum_path, &content, self.fsync && !self.defer_fsync)
+            .map_err(|err| io::Error::new(err.io_error_kind(), err))?;
+        if self.fsync && self.defer_fsync {
+            self.needs_fsync.set(true);
+        }
+        Ok(())
+    }
+
+    /// Write a checksum file from checksums computed elsewhere, without
+    /// opening or hashing the source at `source_path`. `checksum_path`
+    /// defaults to `source_path` with `.sum` appended. Intended for
+    /// migrations; the resulting table only verifies if the provided
+    /// checksums actually match the source contents.
+    ///
+    /// `checksums.len()` must match the chunk count implied by `end` and
+    /// `chunk_size_log`.
+    pub fn write_precomputed(
+        source_path: impl AsRef<Path>,
+        checksum_path: Option<&Path>,
+        chunk_size_log: u32,
+        end: u64,
+        checksums: Vec<u64>,
+        fsync: bool,
+    ) -> Result<(), ChecksumTableError> {
+        if chunk_size_log >= 64 {
+            return Err(ChecksumTableError::InvalidChunkSize(format!(
+                "invalid chunk size logarithm: {}",
+                chunk_size_log
+            )));
+        }
+        let chunk_size = 1u64 << chunk_size_log;
+        let expected = end.div_ceil(chunk_size) as usize;
+        if checksums.len() != expected {
+            return Err(ChecksumTableError::InvalidChunkSize(format!(
+                "checksum count {} does not match the {} chunks implied by end {} and chunk size {}",
+                checksums.len(),
+                expected,
+                end,
+                chunk_size
+            )));
+        }
+        let checksum_path = match checksum_path {
+            Some(path) => path.to_path_buf(),
+            None => path_appendext(source_path.as_ref(), "sum"),
+        };
+        let content = serialize_checksum_file(chunk_size_log, end, &checksums, &[])?;
+        atomic_write_plain(&checksum_path, &content, fsync)
+            .map_err(|err| io::Error::new(err.io_error_kind(), err))?;
+        Ok(())
+    }
+
+    /// Flush the durability barrier deferred by previous updates in
+    /// `defer_fsync` mode. A no-op if nothing is owed.
+    pub fn sync(&mut self) -> Result<(), ChecksumTableError> {
+        if self.needs_fsync.get() {
+            OpenOptions::new()
+                .read(true)
+                .open(&self.checksum_path)?
+                .sync_all()?;
+            self.needs_fsync.set(false);
+        }
+        Ok(())
+    }
+
+    /// Duplicate the table: same source file, checksum file and checksum
+    /// list. The clone inherits the lazily-verified state, so chunks the
+    /// original already verified are not re-hashed by the clone. Use
+    /// [`ChecksumTable::clone_fresh`] to drop that state instead.
+    pub fn clone(&self) -> Result<Self, ChecksumTableError> {
+        Ok(ChecksumTable {
+            path: self.path.clone(),
+            file: self.file.try_clone()?,
+            buf: self.buf.clone(),
+            checksum_path: self.checksum_path.clone(),
+            fsync: self.fsync,
+            defer_fsync: self.defer_fsync,
+            needs_fsync: Cell::new(self.needs_fsync.get()),
+            chunk_size_log: self.chunk_size_log,
+            end: self.end,
+            checksums: self.checksums.clone(),
+            holes: self.holes.clone(),
+            verify_on_update: self.verify_on_update,
+            readonly: self.readonly,
+            checked: RefCell::new(self.checked.borrow().clone()),
+        })
+    }
+
+    /// Like [`ChecksumTable::clone`], but resets the verification state so
+    /// the clone re-hashes every chunk on its next access. Useful when the
+    /// in-memory state is suspect (ex. after possible memory corruption).
+    pub fn clone_fresh(&self) -> Result<Self, ChecksumTableError> {
+        let table = self.clone()?;
+        let chunk_count = table.checksums.len();
+        *table.checked.borrow_mut() = vec![0u64; chunk_count.div_ceil(64)];
+        Ok(table)
+    }
+
+    /// Reset the table to cover nothing and remove the checksum file.
+    pub fn clear(&mut self) -> Result<(), ChecksumTableError> {
+        self.check_writable("clear")?;
+        self.checksums = Checksums::Owned(Vec::new());
+        self.holes = Vec::new();
+        self.end = 0;
+        self.needs_fsync.set(false);
+        *self.checked.borrow_mut() = Vec::new();
+        match fs::remove_file(&self.checksum_path) {
+            Ok(()) => Ok(()),
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::io::Seek;
+    use std::io::SeekFrom;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn setup_source(dir: &Path, content: &[u8]) -> PathBuf {
+        let path = dir.join("source");
+        fs::write(&path, content).expect("write");
+        path
+    }
+
+    fn corrupt_byte(path: &Path, offset: u64) {
+        let mut file = OpenOptions::new().read(true).write(true).open(path).unwrap();
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        let mut byte = [0u8; 1];
+        file.read_exact(&mut byte).unwrap();
+        byte[0] ^= 0xff;
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(&byte).unwrap();
+    }
+
+    #[test]
+    fn test_default_sum_path() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"abcdef");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        table.update(None).unwrap();
+        assert!(path_appendext(&path, "sum").exists());
+        table.check_range(0, 6).unwrap();
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+
+        // Chunk size logarithms of 64 or more are rejected.
+        assert!(matches!(
+            table.update(Some(64)),
+            Err(ChecksumTableError::InvalidChunkSize(_))
+        ));
+
+        // Ranges beyond the covered length are reported as truncation, not
+        // corruption.
+        assert!(matches!(
+            table.check_range(8, 16),
+            Err(ChecksumTableError::Truncated(_))
+        ));
+
+        // A chunk that does not match its checksum is corruption.
+        corrupt_byte(&path, 2);
+        let table = ChecksumTable::new(&path).unwrap();
+        let err = table.check_range(0, 4).unwrap_err();
+        assert!(matches!(err, ChecksumTableError::Corruption(_)));
+
+        // The conversion into the crate error keeps the corruption marker.
+        let crate_err: crate::Error = err.into();
+        assert!(crate_err.is_corruption());
+
+        // A corrupt checksum file is also corruption, caught at open time.
+        corrupt_byte(&path_appendext(&path, "sum"), 3);
+        assert!(matches!(
+            ChecksumTable::new(&path),
+            Err(ChecksumTableError::Corruption(_))
+        ));
+
+        // A missing source file surfaces as a plain IO error.
+        assert!(matches!(
+            ChecksumTable::new(dir.path().join("missing")),
+            Err(ChecksumTableError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_clone_preserves_and_fresh_resets_checked() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"abcdefgh");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        // 4-byte chunks: 2 chunks.
+        table.update(Some(2)).unwrap();
+
+        // Verify the first chunk; it is now marked checked.
+        table.check_range(0, 4).unwrap();
+        assert_eq!(table.checked.borrow()[0] & 1, 1);
+
+        // A plain clone inherits the verification state.
+        let cloned = table.clone().unwrap();
+        assert_eq!(cloned.checked.borrow()[0] & 1, 1);
+
+        // A fresh clone starts unverified and re-hashes the chunk on its
+        // next access.
+        let fresh = table.clone_fresh().unwrap();
+        assert_eq!(fresh.checked.borrow()[0] & 1, 0);
+        fresh.check_range(0, 4).unwrap();
+        assert_eq!(fresh.checked.borrow()[0] & 1, 1);
+
+        // Resetting the clone did not disturb the original.
+        assert_eq!(table.checked.borrow()[0] & 1, 1);
+    }
+
+    #[test]
+    fn test_open_readonly() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+
+        // Verification works as usual in read-only mode.
+        let mut table = ChecksumTable::open_readonly(&path, None).unwrap();
+        table.check_range(0, 16).unwrap();
+        assert!(table.check_range(10, 8).is_err());
+
+        // Mutating methods are rejected with the read-only error.
+        assert!(matches!(
+            table.update(None),
+            Err(ChecksumTableError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            table.update_repair(8),
+            Err(ChecksumTableError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            table.truncate_to(4),
+            Err(ChecksumTableError::ReadOnly(_))
+        ));
+        assert!(matches!(table.clear(), Err(ChecksumTableError::ReadOnly(_))));
+        // The checksum file was not touched.
+        assert!(path_appendext(&path, "sum").exists());
+
+        // A custom checksum path is honored.
+        let sum_path = dir.path().join("custom.sum");
+        fs::copy(path_appendext(&path, "sum"), &sum_path).unwrap();
+        let table = ChecksumTable::open_readonly(&path, Some(&sum_path)).unwrap();
+        table.check_range(0, 16).unwrap();
+    }
+
+    #[test]
+    fn test_custom_checksum_path() {
+        let source_dir = tempdir().unwrap();
+        let sum_dir = tempdir().unwrap();
+        let path = setup_source(source_dir.path(), b"hello, checksummed world");
+        let sum_path = sum_dir.path().join("custom.sum");
+
+        let mut table = ChecksumTable::builder(&path)
+            .checksum_path(&sum_path)
+            .fsync(true)
+            .open()
+            .unwrap();
+        table.update(None).unwrap();
+        table.check_range(0, 24).unwrap();
+        assert!(sum_path.exists());
+        // Nothing was written next to the source.
+        assert!(!path_appendext(&path, "sum").exists());
+
+        // Corruption is caught when reopening with the same custom path.
+        corrupt_byte(&path, 3);
+        let table = ChecksumTable::builder(&path)
+            .checksum_path(&sum_path)
+            .open()
+            .unwrap();
+        assert!(table.check_range(0, 24).is_err());
+    }
+
+    #[test]
+    fn test_update_appends() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"12345678");
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        // Use tiny (4-byte) chunks so multiple chunks are exercised.
+        table.update(Some(2)).unwrap();
+        table.check_range(0, 8).unwrap();
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"abcd")
+            .unwrap();
+        table.update(None).unwrap();
+        table.check_range(0, 12).unwrap();
+
+        // Out-of-range checks fail.
+        assert!(table.check_range(10, 4).is_err());
+    }
+
+    #[test]
+    fn test_covered_len_and_source_len() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"12345678");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        // 4-byte chunks: 2 chunks.
+        table.update(Some(2)).unwrap();
+        assert_eq!(table.covered_len(), 8);
+        assert_eq!(table.source_len().unwrap(), 8);
+
+        // Appending grows the file but not the table, so a caller can
+        // detect "file grew, table is stale" and decide to update.
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"abcd")
+            .unwrap();
+        assert_eq!(table.covered_len(), 8);
+        assert_eq!(table.source_len().unwrap(), 12);
+        assert!(table.source_len().unwrap() > table.covered_len());
+
+        // After update, the table catches up.
+        table.update(None).unwrap();
+        assert_eq!(table.covered_len(), 12);
+        assert_eq!(table.source_len().unwrap(), 12);
+    }
+
+    #[test]
+    fn test_check_ranges() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+
+        // Non-overlapping clean ranges pass.
+        table.check_ranges(&[(0, 4), (8, 4)]).unwrap();
+        // Overlapping ranges are fine too; shared chunks are checked once.
+        table.check_ranges(&[(0, 8), (4, 8), (6, 10)]).unwrap();
+
+        // A single corrupt chunk is still caught through overlapping ranges.
+        corrupt_byte(&path, 13);
+        let table = ChecksumTable::builder(&path).open().unwrap();
+        table.check_ranges(&[(0, 8), (4, 4)]).unwrap();
+        assert!(table.check_ranges(&[(0, 8), (6, 10)]).is_err());
+    }
+
+    #[test]
+    fn test_check_range_against() {
+        let dir = tempdir().unwrap();
+        let content = b"0123456789abcdef";
+        let path = setup_source(dir.path(), content);
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+
+        // An identical external copy verifies cleanly.
+        table.check_range_against(content, 0, 16).unwrap();
+        table.check_range_against(content, 6, 4).unwrap();
+
+        // A corrupt copy is caught, but only for ranges covering the
+        // corrupted chunk.
+        let mut corrupt = content.to_vec();
+        corrupt[13] ^= 0xff;
+        table.check_range_against(&corrupt, 0, 8).unwrap();
+        assert!(table.check_range_against(&corrupt, 8, 8).is_err());
+
+        // A buffer shorter than the covered length is rejected.
+        assert!(table.check_range_against(&content[..8], 0, 4).is_err());
+    }
+
+    #[test]
+    fn test_describe_and_header_only() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+
+        let described = table.describe();
+        assert!(described.contains("chunk size: 4 (log: 2)"));
+        assert!(described.contains("covered bytes: 16"));
+        assert!(described.contains("chunks: 4"));
+        assert!(described.contains("source"));
+
+        let header = ChecksumTable::header_only(path_appendext(&path, "sum")).unwrap();
+        assert_eq!(header.chunk_size_log, 2);
+        assert_eq!(header.end, 16);
+        assert_eq!(header.chunk_count, 4);
+    }
+
+    #[test]
+    fn test_verify_on_update() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::builder(&path)
+            .open()
+            .unwrap()
+            .verify_on_update(true);
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+
+        // Corrupt an early chunk, then append. The append-time verification
+        // catches corruption outside the appended range.
+        corrupt_byte(&path, 1);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"more")
+            .unwrap();
+        let mut table = ChecksumTable::builder(&path)
+            .open()
+            .unwrap()
+            .verify_on_update(true);
+        assert!(table.update(None).is_err());
+
+        // Without the option, the same update succeeds since only new
+        // chunks are hashed.
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        table.update(None).unwrap();
+    }
+
+    #[test]
+    fn test_update_repair() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+
+        // Corrupt chunk 2. A fresh verify-on-update table refuses to
+        // update.
+        corrupt_byte(&path, 9);
+        let mut table = ChecksumTable::builder(&path)
+            .open()
+            .unwrap()
+            .verify_on_update(true);
+        assert!(table.update(None).is_err());
+
+        // A prefix beyond the file length is rejected.
+        assert!(table.update_repair(17).is_err());
+
+        // The caller validated the first 8 bytes independently: repair
+        // rebuilds the rest from the current content.
+        table.update_repair(8).unwrap();
+        table.check_range(0, 16).unwrap();
+        table.update(None).unwrap();
+
+        // Only the trusted prefix was marked verified: corrupting it later
+        // goes unnoticed through the cache, while a fresh table catches it.
+        corrupt_byte(&path, 1);
+        table.check_range(0, 4).unwrap();
+        let fresh = ChecksumTable::builder(&path).open().unwrap();
+        assert!(fresh.check_range(0, 4).is_err());
+        fresh.check_range(4, 12).unwrap();
+    }
+
+    #[test]
+    fn test_truncate_to() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+        table.check_range(0, 16).unwrap();
+        assert_eq!(table.checksums.len(), 4);
+
+        // Growing via truncate_to is rejected.
+        assert!(table.truncate_to(17).is_err());
+
+        table.truncate_to(8).unwrap();
+        assert_eq!(table.checksums.len(), 2);
+        table.check_range(0, 8).unwrap();
+        assert!(table.check_range(0, 12).is_err());
+
+        // Shrink the source on disk and regrow it with different bytes.
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(8)
+            .unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"ABCDEFGH")
+            .unwrap();
+
+        // Corrupt a byte in the kept prefix. The prefix chunks stayed
+        // verified across truncate_to, so update only hashes the regrown
+        // tail and does not notice.
+        corrupt_byte(&path, 2);
+        table.update(None).unwrap();
+        assert_eq!(table.checksums.len(), 4);
+        table.check_range(0, 16).unwrap();
+
+        // A fresh table re-hashes everything and catches the corruption.
+        let mut fresh = ChecksumTable::builder(&path).open().unwrap();
+        assert!(fresh.check_range(0, 4).is_err());
+        fresh.check_range(8, 8).unwrap();
+
+        // Truncating mid-chunk keeps a re-hashed partial last chunk.
+        fresh.truncate_to(6).unwrap();
+        assert_eq!(fresh.checksums.len(), 2);
+        fresh.check_range(4, 2).unwrap();
+    }
+
+    #[test]
+    fn test_mark_holes() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        // 4-byte chunks: 4 chunks. Bytes 4..12 (chunks 1 and 2) are a hole,
+        // declared before anything is hashed. Adjacent ranges are merged.
+        table.mark_holes(&[(4, 4), (8, 4)]).unwrap();
+        assert_eq!(table.holes, vec![(4, 8)]);
+        table.update(Some(2)).unwrap();
+        table.check_range(0, 16).unwrap();
+
+        // Corruption inside the hole goes unnoticed even by a fresh table:
+        // the hole list is persisted in the checksum file and the hole is
+        // never hashed.
+        corrupt_byte(&path, 6);
+        let fresh = ChecksumTable::new(&path).unwrap();
+        assert_eq!(fresh.holes, vec![(4, 8)]);
+        fresh.check_range(0, 16).unwrap();
+        fresh.check_range(4, 8).unwrap();
+        assert_eq!(fresh.iter_failed_chunks().count(), 0);
+
+        // update in verify-everything mode does not hash the hole either.
+        let mut fresh = ChecksumTable::new(&path).unwrap().verify_on_update(true);
+        fresh.update(None).unwrap();
+
+        // Corruption outside the hole is still caught.
+        corrupt_byte(&path, 13);
+        let fresh = ChecksumTable::new(&path).unwrap();
+        fresh.check_range(4, 8).unwrap();
+        assert!(fresh.check_range(12, 4).is_err());
+
+        // header_only understands the versioned file.
+        let header = ChecksumTable::header_only(path_appendext(&path, "sum")).unwrap();
+        assert_eq!(header.chunk_size_log, 2);
+        assert_eq!(header.end, 16);
+        assert_eq!(header.chunk_count, 4);
+
+        // Read-only tables reject hole declarations.
+        let mut readonly = ChecksumTable::open_readonly(&path, None).unwrap();
+        assert!(matches!(
+            readonly.mark_holes(&[(0, 4)]),
+            Err(ChecksumTableError::ReadOnly(_))
+        ));
+
+        // A chunk only partially inside a hole is hashed as usual.
+        let path = dir.path().join("source2");
+        fs::write(&path, b"0123456789abcdef").unwrap();
+        let mut table = ChecksumTable::new(&path).unwrap();
+        // The hole straddles chunks 0 and 1 without containing either.
+        table.mark_holes(&[(2, 4)]).unwrap();
+        table.update(Some(2)).unwrap();
+        corrupt_byte(&path, 3);
+        let fresh = ChecksumTable::new(&path).unwrap();
+        assert!(fresh.check_range(0, 4).is_err());
+    }
+
+    #[test]
+    fn test_mmap_checksums() {
+        let dir = tempdir().unwrap();
+        // 64 bytes with 4-byte chunks: 16 chunks.
+        let content: Vec<u8> = (0u8..64).collect();
+        let path = setup_source(dir.path(), &content);
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        table.update(Some(2)).unwrap();
+
+        let owned = ChecksumTable::builder(&path).open().unwrap();
+        let mapped = ChecksumTable::builder(&path)
+            .mmap_checksums(true)
+            .open()
+            .unwrap();
+        assert_eq!(owned.checksums.len(), 16);
+        assert_eq!(mapped.checksums.len(), 16);
+        owned.check_range(0, 64).unwrap();
+        mapped.check_range(0, 64).unwrap();
+
+        // Both modes report identical verification results on a corrupted
+        // source.
+        corrupt_byte(&path, 5);
+        corrupt_byte(&path, 42);
+        let owned = ChecksumTable::builder(&path).open().unwrap();
+        let mapped = ChecksumTable::builder(&path)
+            .mmap_checksums(true)
+            .open()
+            .unwrap();
+        let owned_failed: Vec<FailedChunk> = owned.iter_failed_chunks().collect();
+        let mapped_failed: Vec<FailedChunk> = mapped.iter_failed_chunks().collect();
+        assert_eq!(owned_failed, mapped_failed);
+        assert_eq!(owned_failed.len(), 2);
+        assert!(mapped.check_range(4, 4).is_err());
+        mapped.check_range(8, 32).unwrap();
+
+        // A corrupt checksum file is rejected at open time in mmap mode too.
+        corrupt_byte(&path_appendext(&path, "sum"), 3);
+        assert!(
+            ChecksumTable::builder(&path)
+                .mmap_checksums(true)
+                .open()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_iter_failed_chunks() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"0123456789abcdef");
+        let mut table = ChecksumTable::builder(&path).open().unwrap();
+        // 4-byte chunks: 4 chunks in total.
+        table.update(Some(2)).unwrap();
+        assert_eq!(table.iter_failed_chunks().count(), 0);
+
+        // Corrupt two separate chunks. Both show up in one pass, while
+        // check_range stops at the first.
+        corrupt_byte(&path, 2);
+        corrupt_byte(&path, 13);
+        let table = ChecksumTable::builder(&path).open().unwrap();
+        let failed: Vec<FailedChunk> = table.iter_failed_chunks().collect();
+        assert_eq!(
+            failed,
+            vec![
+                FailedChunk {
+                    index: 0,
+                    start: 0,
+                    end: 4
+                },
+                FailedChunk {
+                    index: 3,
+                    start: 12,
+                    end: 16
+                },
+            ]
+        );
+
+        // The audit did not mark anything as checked: clean chunks still
+        // verify and corrupt chunks still fail afterwards.
+        table.check_range(4, 8).unwrap();
+        assert!(table.check_range(0, 4).is_err());
+        assert!(table.check_range(12, 4).is_err());
+    }
+
+    #[test]
+    fn test_write_precomputed() {
+        let dir = tempdir().unwrap();
+        let content = b"0123456789abcdef";
+        let path = setup_source(dir.path(), content);
+
+        // Checksums computed elsewhere, for 4-byte chunks.
+        let checksums: Vec<u64> = content.chunks(4).map(xxhash).collect();
+
+        // A count mismatching the implied chunk count is rejected.
+        assert!(
+            ChecksumTable::write_precomputed(&path, None, 2, 16, checksums[..3].to_vec(), false)
+                .is_err()
+        );
+
+        ChecksumTable::write_precomputed(&path, None, 2, 16, checksums, false).unwrap();
+
+        // The written table round-trips: a normal open verifies the real
+        // file contents against the precomputed checksums.
+        let table = ChecksumTable::new(&path).unwrap();
+        table.check_range(0, 16).unwrap();
+
+        // Checksums that do not match the source are detected as usual.
+        let bogus = vec![0u64; 4];
+        let sum_path = dir.path().join("bogus.sum");
+        ChecksumTable::write_precomputed(&path, Some(&sum_path), 2, 16, bogus, false).unwrap();
+        let table = ChecksumTable::builder(&path)
+            .checksum_path(&sum_path)
+            .open()
+            .unwrap();
+        assert!(table.check_range(0, 16).is_err());
+    }
+
+    #[test]
+    fn test_deferred_fsync() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"1234");
+        let mut table = ChecksumTable::builder(&path)
+            .fsync(true)
+            .defer_fsync(true)
+            .open()
+            .unwrap();
+        // Several small appends, each updating the table; the fsync is owed
+        // but not paid per update.
+        table.update(Some(2)).unwrap();
+        for chunk in [b"5678", b"abcd", b"efgh"] {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap()
+                .write_all(chunk)
+                .unwrap();
+            table.update(None).unwrap();
+            assert!(table.needs_fsync.get());
+        }
+
+        // One sync settles the batch; another is a no-op.
+        table.sync().unwrap();
+        assert!(!table.needs_fsync.get());
+        table.sync().unwrap();
+
+        // The rewritten table reloads correctly and covers everything.
+        let fresh = ChecksumTable::builder(&path).open().unwrap();
+        fresh.check_range(0, 16).unwrap();
+        assert_eq!(fresh.checksums.len(), 4);
+    }
+
+    #[test]
+    fn test_clear() {
+        let dir = tempdir().unwrap();
+        let path = setup_source(dir.path(), b"content");
+        let mut table = ChecksumTable::new(&path).unwrap();
+        table.update(None).unwrap();
+        table.clear().unwrap();
+        assert!(!path_appendext(&path, "sum").exists());
+        assert!(table.check_range(0, 7).is_err());
+    }
+}
@@ -20,6 +20,7 @@ mod macros;
 
 pub mod base16;
 mod change_detect;
+pub mod checksum_table;
 pub mod config;
 mod errors;
 pub mod index;
@@ -34,6 +35,8 @@ pub mod utils;
 #[cfg(all(unix, feature = "sigbus-handler"))]
 mod sigbus;
 
+pub use checksum_table::ChecksumTable;
+pub use checksum_table::ChecksumTableError;
 pub use errors::Error;
 pub use errors::Result;
 pub use repair::DefaultOpenOptions;
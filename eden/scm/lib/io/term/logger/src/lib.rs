@@ -5,17 +5,36 @@
  * GNU General Public License version 2.
  */
 
+use std::collections::BTreeMap;
 use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use io::IO;
 use lazystr::LazyStr;
 
+/// Effective verbosity of a [`TermLogger`], from least to most output.
+/// The base level is derived from the `quiet` and `verbose` flags;
+/// per-module overrides can raise or lower it for messages logged through
+/// [`TermLogger::info_for`] and [`TermLogger::verbose_for`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Only warnings are written.
+    Quiet,
+    /// Normal (`info`) output is written; `verbose` output is dropped.
+    Info,
+    /// Both normal and verbose output are written.
+    Verbose,
+}
+
 /// TermLogger mixes the IO object with knowledge of output verbosity.
 #[derive(Clone)]
 pub struct TermLogger {
     io: IO,
     quiet: bool,
     verbose: bool,
+    metadata: Option<Arc<Mutex<BTreeMap<String, String>>>>,
+    module_levels: Option<Arc<BTreeMap<String, Level>>>,
 }
 
 impl TermLogger {
@@ -24,6 +43,8 @@ impl TermLogger {
             io: io.clone(),
             quiet: false,
             verbose: false,
+            metadata: None,
+            module_levels: None,
         }
     }
 
@@ -32,9 +53,18 @@ impl TermLogger {
             io: IO::null(),
             quiet: false,
             verbose: false,
+            metadata: None,
+            module_levels: None,
         }
     }
 
+    /// Redirect this logger to `io`, keeping the verbosity flags, the
+    /// metadata map and any per-module level overrides.
+    pub fn with_io(mut self, io: &IO) -> Self {
+        self.io = io.clone();
+        self
+    }
+
     pub fn with_quiet(mut self, quiet: bool) -> Self {
         self.quiet = quiet;
         self
@@ -45,22 +75,87 @@ impl TermLogger {
         self
     }
 
+    /// Render the given metadata as a `[k=v ...]` prefix on log lines.
+    /// The map is shared, so entries added after the logger was
+    /// constructed show up on subsequent lines.
+    pub fn with_metadata(mut self, metadata: Arc<Mutex<BTreeMap<String, String>>>) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Override the effective level for specific modules, applied by the
+    /// module-aware [`TermLogger::info_for`] and
+    /// [`TermLogger::verbose_for`]. An override for `"dag"` also applies
+    /// to submodules like `"dag::namedag"`, unless a more specific
+    /// override exists. Replaces any previous overrides.
+    pub fn with_module_levels(mut self, levels: &[(&str, Level)]) -> Self {
+        let levels: BTreeMap<String, Level> = levels
+            .iter()
+            .map(|(module, level)| (module.to_string(), *level))
+            .collect();
+        self.module_levels = Some(Arc::new(levels));
+        self
+    }
+
+    /// The level applied to messages attributed to `module`: the most
+    /// specific matching override, or the base level from the quiet and
+    /// verbose flags.
+    fn level_for(&self, module: &str) -> Level {
+        if let Some(levels) = &self.module_levels {
+            let mut candidate = module;
+            loop {
+                if let Some(level) = levels.get(candidate) {
+                    return *level;
+                }
+                match candidate.rfind("::") {
+                    Some(pos) => candidate = &candidate[..pos],
+                    None => break,
+                }
+            }
+        }
+        if self.quiet {
+            Level::Quiet
+        } else if self.verbose {
+            Level::Verbose
+        } else {
+            Level::Info
+        }
+    }
+
+    /// Like [`TermLogger::info`], but attributed to `module` so
+    /// per-module level overrides apply.
+    pub fn info_for(&self, module: &str, msg: impl LazyStr) {
+        if self.level_for(module) >= Level::Info {
+            self.write_with_metadata(self.io.output(), msg.to_str())
+        }
+    }
+
+    /// Like [`TermLogger::verbose`], but attributed to `module` so
+    /// per-module level overrides apply: the message is written when the
+    /// module's effective level is `Verbose`, even if the logger itself
+    /// is not verbose.
+    pub fn verbose_for(&self, module: &str, msg: impl LazyStr) {
+        if self.level_for(module) >= Level::Verbose {
+            self.write_with_metadata(self.io.output(), msg.to_str())
+        }
+    }
+
     /// Write to stdout if not --quiet.
     pub fn info(&self, msg: impl LazyStr) {
         if !self.quiet {
-            Self::write(self.io.output(), msg.to_str())
+            self.write_with_metadata(self.io.output(), msg.to_str())
         }
     }
 
     /// Write to stderr.
     pub fn warn(&self, msg: impl AsRef<str>) {
-        Self::write(self.io.error(), msg)
+        self.write_with_metadata(self.io.error(), msg)
     }
 
     /// Write to stdout if --verbose.
     pub fn verbose(&self, msg: impl LazyStr) {
         if self.verbose {
-            Self::write(self.io.output(), msg.to_str())
+            self.write_with_metadata(self.io.output(), msg.to_str())
         }
     }
 
@@ -77,6 +172,29 @@ impl TermLogger {
         &self.io
     }
 
+    fn write_with_metadata(&self, w: impl Write, msg: impl AsRef<str>) {
+        match self.rendered_metadata() {
+            Some(prefix) => Self::write(w, format!("{}{}", prefix, msg.as_ref())),
+            None => Self::write(w, msg),
+        }
+    }
+
+    /// Render the metadata map as a `[k=v ...] ` prefix, or None if there
+    /// is no metadata to show.
+    fn rendered_metadata(&self) -> Option<String> {
+        let metadata = self.metadata.as_ref()?;
+        let metadata = metadata.lock().expect("poisoned lock");
+        if metadata.is_empty() {
+            return None;
+        }
+        let rendered = metadata
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Some(format!("[{}] ", rendered))
+    }
+
     fn write(mut w: impl Write, msg: impl AsRef<str>) {
         let msg = identity::default().punch(msg.as_ref());
 
@@ -131,6 +249,36 @@ mod test {
         assert_eq!(get_stdout(&io), "okay\n");
     }
 
+    #[test]
+    fn test_module_levels() {
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+        let logger = TermLogger::new(&io)
+            .with_module_levels(&[("dag", Level::Verbose), ("revlog", Level::Quiet)]);
+        // The override raises "dag" (and its submodules) to verbose while
+        // the base level stays at Info.
+        logger.verbose_for("dag", "emitted");
+        logger.verbose_for("dag::namedag", "submodule emitted");
+        logger.verbose_for("config", "dropped");
+        // A Quiet override silences normal output for that module only.
+        logger.info_for("revlog", "dropped");
+        logger.info_for("config", "emitted too");
+        assert_eq!(get_stdout(&io), "emitted\nsubmodule emitted\nemitted too\n");
+    }
+
+    #[test]
+    fn test_metadata_prefix() {
+        let io = IO::new("".as_bytes(), Vec::new(), Some(Vec::new()));
+        let metadata = Arc::new(Mutex::new(BTreeMap::new()));
+        let logger = TermLogger::new(&io).with_metadata(metadata.clone());
+        logger.info("plain");
+        metadata
+            .lock()
+            .unwrap()
+            .insert("repo".to_string(), "foo".to_string());
+        logger.info("status");
+        assert_eq!(get_stdout(&io), "plain\n[repo=foo] status\n");
+    }
+
     fn get_stdout(io: &IO) -> String {
         let stdout = io.with_output(|o| o.as_any().downcast_ref::<Vec<u8>>().unwrap().clone());
         String::from_utf8(stdout).unwrap()
@@ -48,7 +48,7 @@ pub fn flush(
     treestate: &mut TreeState,
     locker: &RepoLocker,
     write_time: Option<i64>,
-    lock_timeout_secs: Option<u32>,
+    lock_timeout: Option<Duration>,
 ) -> Result<()> {
     if treestate.dirty() {
         tracing::debug!("flushing dirty treestate");
@@ -56,7 +56,7 @@ pub fn flush(
         let dot_dir = root.join(id.dot_dir());
         let dirstate_path = dot_dir.join("dirstate");
 
-        let _lock = wait_for_wc_lock(dot_dir, locker, lock_timeout_secs)?;
+        let _lock = wait_for_wc_lock(dot_dir, locker, lock_timeout)?;
 
         let dirstate_input = fs_err::read(&dirstate_path)?;
         let mut dirstate = Dirstate::deserialize(&mut dirstate_input.as_slice())?;
@@ -71,11 +71,16 @@ pub fn flush(
         // In that situation, just return an error and the client can decide if that's ok or not.
 
         if let Some(dirstate_fields) = &dirstate.tree_state {
-            if treestate.file_name()? != dirstate_fields.tree_filename
-                || treestate.original_root_id() != dirstate_fields.tree_root_id
-            {
+            if treestate.file_name()? != dirstate_fields.tree_filename {
+                // The treestate file itself was replaced (e.g. a repack).
+                // Reloading our in-memory state won't help.
                 return Err(ErrorKind::TreestateOutOfDate.into());
             }
+            if treestate.original_root_id() != dirstate_fields.tree_root_id {
+                // Same treestate file, newer root: another process appended
+                // an update. The caller can reload and retry.
+                return Err(ErrorKind::TreestateConcurrentModification.into());
+            }
         }
 
         let metadata = treestate.metadata()?;
@@ -124,28 +129,50 @@ pub fn flush(
     }
 }
 
+/// Try to take the working copy lock, waiting up to `timeout`. `None` waits
+/// indefinitely (delegating to the blocking `lock_working_copy`). On timeout
+/// the returned [`ErrorKind::LockTimeout`] carries both the configured
+/// timeout and the actual wait, so callers can log the configuration that
+/// produced it.
 pub fn wait_for_wc_lock(
     wc_dot_hg: PathBuf,
     locker: &RepoLocker,
-    timeout_secs: Option<u32>,
+    timeout: Option<Duration>,
 ) -> anyhow::Result<LockedPath> {
-    let mut timeout = match timeout_secs {
+    let timeout = match timeout {
         None => return Ok(locker.lock_working_copy(wc_dot_hg)?),
         Some(timeout) => timeout,
     };
 
+    // Poll well below the timeout granularity so the error fires close to
+    // the configured boundary instead of on whole-second steps.
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    let start = std::time::Instant::now();
+
     loop {
         match locker.try_lock_working_copy(wc_dot_hg.clone()) {
             Ok(lock) => return Ok(lock),
             Err(err) => match err {
-                LockError::Contended(_) => {
-                    if timeout == 0 {
-                        return Err(ErrorKind::LockTimeout.into());
+                LockError::Contended(ref contended) => {
+                    let waited = start.elapsed();
+                    if waited >= timeout {
+                        // The lock file records who is holding the lock
+                        // (typically "host:pid").
+                        let holder = if contended.contents.is_empty() {
+                            None
+                        } else {
+                            Some(util::utf8::escape_non_utf8(&contended.contents))
+                        };
+                        return Err(ErrorKind::LockTimeout {
+                            waited,
+                            timeout,
+                            holder,
+                        }
+                        .into());
                     }
 
-                    timeout -= 1;
-
-                    std::thread::sleep(Duration::from_secs(1));
+                    std::thread::sleep(POLL_INTERVAL.min(timeout - waited));
                 }
                 _ => return Err(err.into()),
             },
@@ -201,4 +228,49 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_error_retryability() {
+        // A concurrent root update is safe to retry after reloading; a
+        // replaced treestate file is not.
+        assert!(ErrorKind::TreestateConcurrentModification.is_retryable());
+        assert!(!ErrorKind::TreestateOutOfDate.is_retryable());
+    }
+
+    #[test]
+    fn test_wait_for_wc_lock_timeout() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let cfg = std::collections::BTreeMap::<&str, &str>::new();
+
+        // Hold the working copy lock from a different locker so
+        // wait_for_wc_lock sees contention.
+        let other = RepoLocker::new(&cfg, tmp.path().to_path_buf())?;
+        let _lock = other.lock_working_copy(tmp.path().to_path_buf())?;
+
+        // A short timeout fires near the configured boundary, not on whole
+        // second steps, and the error reports the configuration back.
+        let configured = Duration::from_millis(250);
+        let locker = RepoLocker::new(&cfg, tmp.path().to_path_buf())?;
+        let start = std::time::Instant::now();
+        let err =
+            wait_for_wc_lock(tmp.path().to_path_buf(), &locker, Some(configured)).unwrap_err();
+        let elapsed = start.elapsed();
+        match err.downcast::<ErrorKind>() {
+            Ok(ErrorKind::LockTimeout {
+                waited,
+                timeout,
+                holder,
+            }) => {
+                assert_eq!(timeout, configured);
+                assert!(waited >= configured);
+                // The wait should not overshoot by a whole polling era.
+                assert!(elapsed < configured + Duration::from_secs(1));
+                // The lock file records the holder (host:pid).
+                assert!(holder.is_some());
+            }
+            other => panic!("expected LockTimeout, got {:?}", other),
+        }
+
+        Ok(())
+    }
 }
@@ -8,6 +8,7 @@
 //! Errors.
 
 use std::path::PathBuf;
+use std::time::Duration;
 
 use thiserror::Error;
 
@@ -19,16 +20,87 @@ pub enum ErrorKind {
     UnsupportedTreeVersion(u32),
     #[error("store file version not supported: {0}")]
     UnsupportedVersion(u32),
+    #[error("failed to migrate from version {from} to version {to}: {reason}")]
+    MigrationFailed { from: u32, to: u32, reason: String },
     #[error("invalid store id: {0}")]
     InvalidStoreId(u64),
     #[error("store is read-only")]
     ReadOnlyStore,
     #[error("treedirstate is corrupt")]
     CorruptTree,
+    #[error(
+        "checksum mismatch at offset {offset}: expected {expected:#018x}, found {actual:#018x}"
+    )]
+    ChecksumMismatch {
+        offset: u64,
+        expected: u64,
+        actual: u64,
+    },
     #[error("callback error: {0}")]
     CallbackError(String),
     #[error("dirstate/treestate was out of date and therefore did not flush")]
     TreestateOutOfDate,
-    #[error("timed out waiting for working copy lock")]
-    LockTimeout,
+    #[error("treestate was modified concurrently and therefore did not flush (retry is safe)")]
+    TreestateConcurrentModification,
+    #[error(
+        "timed out after {waited:?} (configured timeout: {timeout:?}) waiting for working copy lock{}",
+        .holder.as_ref().map_or_else(String::new, |h| format!(" held by {}", h))
+    )]
+    LockTimeout {
+        /// How long we actually waited. At least `timeout`.
+        waited: Duration,
+        /// The configured timeout, readable back for logging.
+        timeout: Duration,
+        holder: Option<String>,
+    },
+}
+
+impl ErrorKind {
+    /// Whether the operation that produced this error is safe to retry
+    /// after reloading the on-disk state.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorKind::TreestateConcurrentModification)
+    }
+
+    /// Wrap a failure that happened while upgrading on-disk state from
+    /// version `from` to version `to`. Unlike `UnsupportedVersion` and
+    /// `UnsupportedTreeVersion` ("can't read this version at all"), this
+    /// signals that an automatic upgrade was attempted and broke partway.
+    pub fn migration_failed(from: u32, to: u32, reason: impl std::fmt::Display) -> Self {
+        ErrorKind::MigrationFailed {
+            from,
+            to,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stand-in for an upgrade path that fails partway through.
+    fn failing_migration(from: u32, to: u32) -> Result<(), ErrorKind> {
+        Err(ErrorKind::migration_failed(
+            from,
+            to,
+            "checksum mismatch in block 3",
+        ))
+    }
+
+    #[test]
+    fn test_migration_failed_carries_versions() {
+        let err = failing_migration(1, 2).unwrap_err();
+        match &err {
+            ErrorKind::MigrationFailed { from, to, .. } => {
+                assert_eq!((*from, *to), (1, 2));
+            }
+            _ => panic!("expected MigrationFailed, got {:?}", err),
+        }
+        // The message names both versions.
+        let msg = err.to_string();
+        assert!(msg.contains("version 1"));
+        assert!(msg.contains("version 2"));
+        assert!(!err.is_retryable());
+    }
 }
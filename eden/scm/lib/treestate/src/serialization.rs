@@ -237,8 +237,15 @@ impl Serializable for TreeStateRoot {
         let mut buf = Vec::new();
         r.read_to_end(&mut buf)?;
 
-        if xxhash(&buf) != checksum {
-            bail!(ErrorKind::CorruptTree);
+        let actual = xxhash(&buf);
+        if actual != checksum {
+            // The offset is relative to the start of the root block; the
+            // caller translates it into a file offset.
+            bail!(ErrorKind::ChecksumMismatch {
+                offset: 0,
+                expected: checksum,
+                actual,
+            });
         }
 
         let mut cur = Cursor::new(buf);
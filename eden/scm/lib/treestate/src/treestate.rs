@@ -22,6 +22,7 @@ use anyhow::Result;
 use types::HgId;
 use util::path::create_dir;
 
+use crate::errors::ErrorKind;
 use crate::filestate::FileStateV2;
 use crate::filestate::StateFlags;
 use crate::filestore::FileStore;
@@ -71,7 +72,26 @@ impl TreeState {
             tracing::trace!(target: "treestate::open", "reading root data");
             let mut root_buf = Cursor::new(store.read(root_id)?);
             tracing::trace!(target: "treestate::open", "deserializing root data");
-            TreeStateRoot::deserialize(&mut root_buf)?
+            TreeStateRoot::deserialize(&mut root_buf).map_err(|err| {
+                match err.downcast::<ErrorKind>() {
+                    Ok(ErrorKind::ChecksumMismatch {
+                        offset,
+                        expected,
+                        actual,
+                    }) => {
+                        // Translate the block-relative offset into a file
+                        // offset (the block starts with a 4-byte length).
+                        ErrorKind::ChecksumMismatch {
+                            offset: root_id.0 + 4 + offset,
+                            expected,
+                            actual,
+                        }
+                        .into()
+                    }
+                    Ok(kind) => kind.into(),
+                    Err(err) => err,
+                }
+            })?
         };
         tracing::trace!(target: "treestate::open", "constructing tree");
         let tree = Tree::open(root.tree_block_id(), root.file_count());
@@ -593,6 +613,35 @@ mod tests {
         assert_eq!(state.len(), 0);
     }
 
+    #[test]
+    fn test_corrupt_root_checksum() {
+        let dir = tempdir().expect("tempdir");
+        let mut state = TreeState::new(dir.path(), true).expect("open").0;
+        let block_id = state.flush().expect("flush");
+        let path = dir.path().join(state.file_name().unwrap());
+        drop(state);
+
+        // Flip a byte inside the checksummed root data. The stored checksum
+        // occupies the first 8 bytes of the block, after the 4-byte length.
+        let mut buf = std::fs::read(&path).expect("read");
+        let corrupt_at = (block_id.0 + 4 + 8) as usize;
+        buf[corrupt_at] ^= 0xff;
+        std::fs::write(&path, buf).expect("write");
+
+        let err = TreeState::open(&path, block_id, true).unwrap_err();
+        match err.downcast::<ErrorKind>() {
+            Ok(ErrorKind::ChecksumMismatch {
+                offset,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(offset, block_id.0 + 4);
+                assert_ne!(expected, actual);
+            }
+            other => panic!("expected ChecksumMismatch, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_empty_write_as() {
         let dir = tempdir().expect("tempdir");
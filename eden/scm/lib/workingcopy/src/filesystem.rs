@@ -13,6 +13,7 @@ pub mod watchmanfs;
 pub use dotgit::DotGitFileSystem;
 pub use filesystem::FileSystem;
 pub use filesystem::PendingChange;
+pub use filesystem::PendingChanges;
 pub use physicalfs::PhysicalFileSystem;
 pub use watchmanfs::WatchmanFileSystem;
 
@@ -27,4 +28,141 @@ pub enum FileSystemType {
     Watchman,
     Eden,
     DotGit,
+    /// Git-backed working copy. Pending changes are produced from the git
+    /// index and worktree (via the same CLI-based implementation as
+    /// `DotGit`).
+    Git,
+}
+
+impl FileSystemType {
+    /// Stable lowercase name, suitable for logging and config values.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileSystemType::Normal => "normal",
+            FileSystemType::Watchman => "watchman",
+            FileSystemType::Eden => "eden",
+            FileSystemType::DotGit => "dotgit",
+            FileSystemType::Git => "git",
+        }
+    }
+
+    /// Detect the filesystem type from the contents of the dot dir
+    /// (ex. ".hg" or ".sl").
+    ///
+    /// Inspects the "requires" file and well-known markers. Returns
+    /// `Normal` if no marker indicates a specialized backend. A missing
+    /// "requires" file is not an error, but an unreadable dot dir is.
+    pub fn detect(dot_dir: &std::path::Path) -> std::io::Result<FileSystemType> {
+        use std::io;
+
+        if !dot_dir.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", dot_dir.display()),
+            ));
+        }
+
+        let requires = match fs_err::read_to_string(dot_dir.join("requires")) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let has_requirement = |name: &str| requires.lines().any(|line| line.trim() == name);
+
+        // The ".eden" marker directory lives in the working copy root,
+        // next to the dot dir.
+        let is_eden = has_requirement("eden")
+            || dot_dir
+                .parent()
+                .is_some_and(|root| root.join(".eden").is_dir());
+        if is_eden {
+            Ok(FileSystemType::Eden)
+        } else if has_requirement("dotgit") {
+            Ok(FileSystemType::DotGit)
+        } else if has_requirement("git") {
+            Ok(FileSystemType::Git)
+        } else if dot_dir.join("watchmanstate").exists() {
+            Ok(FileSystemType::Watchman)
+        } else {
+            Ok(FileSystemType::Normal)
+        }
+    }
+}
+
+impl std::fmt::Display for FileSystemType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for FileSystemType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(FileSystemType::Normal),
+            "watchman" => Ok(FileSystemType::Watchman),
+            "eden" => Ok(FileSystemType::Eden),
+            "dotgit" => Ok(FileSystemType::DotGit),
+            "git" => Ok(FileSystemType::Git),
+            _ => Err(anyhow::anyhow!("unknown filesystem type: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_variant_empty_pending_changes() {
+        let fs_type = FileSystemType::Git;
+        assert!(fs_type == FileSystemType::Git);
+
+        // An empty change set iterates cleanly.
+        let changes: Vec<anyhow::Result<PendingChange>> = Vec::new();
+        assert_eq!(changes.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_name_round_trip() {
+        for fs_type in [
+            FileSystemType::Normal,
+            FileSystemType::Watchman,
+            FileSystemType::Eden,
+            FileSystemType::DotGit,
+            FileSystemType::Git,
+        ] {
+            let name = fs_type.to_string();
+            assert_eq!(name, fs_type.as_str());
+            assert!(name.parse::<FileSystemType>().unwrap() == fs_type);
+        }
+        assert!("fuse".parse::<FileSystemType>().is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        let dot_dir = dir.path().join(".sl");
+
+        // Missing dot dir is an error, not a panic.
+        assert!(FileSystemType::detect(&dot_dir).is_err());
+
+        std::fs::create_dir(&dot_dir).unwrap();
+        assert!(FileSystemType::detect(&dot_dir).unwrap() == FileSystemType::Normal);
+
+        std::fs::write(dot_dir.join("watchmanstate"), b"").unwrap();
+        assert!(FileSystemType::detect(&dot_dir).unwrap() == FileSystemType::Watchman);
+
+        std::fs::write(dot_dir.join("requires"), b"store\ndotgit\n").unwrap();
+        assert!(FileSystemType::detect(&dot_dir).unwrap() == FileSystemType::DotGit);
+
+        std::fs::write(dot_dir.join("requires"), b"store\neden\n").unwrap();
+        assert!(FileSystemType::detect(&dot_dir).unwrap() == FileSystemType::Eden);
+
+        // The ".eden" marker wins even without the requirement.
+        std::fs::write(dot_dir.join("requires"), b"store\n").unwrap();
+        std::fs::create_dir(dir.path().join(".eden")).unwrap();
+        assert!(FileSystemType::detect(&dot_dir).unwrap() == FileSystemType::Eden);
+    }
 }
@@ -44,6 +44,65 @@ impl PendingChange {
     }
 }
 
+/// Iterator adaptor over `PendingChange` items, optionally restricted to
+/// entries under a path prefix.
+pub struct PendingChanges {
+    inner: Box<dyn Iterator<Item = Result<PendingChange>>>,
+    prefix: Option<RepoPathBuf>,
+    case_sensitive: bool,
+}
+
+impl PendingChanges {
+    pub fn new(inner: Box<dyn Iterator<Item = Result<PendingChange>>>) -> Self {
+        Self {
+            inner,
+            prefix: None,
+            case_sensitive: true,
+        }
+    }
+
+    /// Restrict emitted entries to those under `prefix`. An entry whose path
+    /// equals `prefix` is included.
+    pub fn with_path_prefix(mut self, prefix: RepoPathBuf) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    /// Control case sensitivity of the prefix comparison. Defaults to
+    /// case-sensitive.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+}
+
+impl Iterator for PendingChanges {
+    type Item = Result<PendingChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let item = self.inner.next()?;
+            let prefix = match &self.prefix {
+                Some(prefix) => prefix,
+                None => return Some(item),
+            };
+            match &item {
+                Ok(change) => {
+                    // `starts_with` includes the path equal to the prefix.
+                    if change
+                        .get_path()
+                        .starts_with(prefix, self.case_sensitive)
+                    {
+                        return Some(item);
+                    }
+                }
+                // Propagate errors regardless of the filter.
+                Err(_) => return Some(item),
+            }
+        }
+    }
+}
+
 pub trait FileSystem {
     fn pending_changes(
         &self,
@@ -97,3 +156,29 @@ pub trait FileSystem {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> RepoPathBuf {
+        RepoPathBuf::from_string(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_with_path_prefix() {
+        let changes: Vec<Result<PendingChange>> = vec![
+            Ok(PendingChange::Changed(path("dir/a"))),
+            Ok(PendingChange::Deleted(path("dir/sub/b"))),
+            Ok(PendingChange::Changed(path("dir"))),
+            Ok(PendingChange::Changed(path("other/c"))),
+            Ok(PendingChange::Changed(path("dirx/d"))),
+        ];
+        let filtered: Vec<_> = PendingChanges::new(Box::new(changes.into_iter()))
+            .with_path_prefix(path("dir"))
+            .map(|c| c.unwrap().get_path().to_string())
+            .collect();
+        // "dir" itself is included; "dirx/d" is not under "dir".
+        assert_eq!(filtered, vec!["dir/a", "dir/sub/b", "dir"]);
+    }
+}
@@ -317,7 +317,7 @@ impl WorkingCopy {
                     )?)
                 }
             }
-            FileSystemType::DotGit => Box::new(DotGitFileSystem::new(
+            FileSystemType::DotGit | FileSystemType::Git => Box::new(DotGitFileSystem::new(
                 vfs.clone(),
                 dot_dir,
                 store.clone(),
@@ -12,6 +12,7 @@ use std::cell::RefCell;
 use ::clientinfo as client_info;
 use cpython::*;
 use cpython_ext::convert::Serde;
+use cpython_ext::PyNone;
 use cpython_ext::ResultPyErrExt;
 
 pub fn init_module(py: Python, package: &str) -> PyResult<PyModule> {
@@ -60,6 +61,23 @@ py_class!(pub class clientinfo |py| {
     def to_json(&self) -> PyResult<PyBytes> {
         convert(py, self.clientinfo(py).borrow().to_json().map(|s| s.into_bytes()))
     }
+
+    /// The detected coarse host environment (e.g. "sandcastle", "devserver").
+    def environment(&self) -> PyResult<String> {
+        Ok(self.clientinfo(py).borrow().environment.to_string())
+    }
+
+    /// Stable numeric hash of the client identity, for sampling decisions.
+    def sampling_key(&self) -> PyResult<u64> {
+        Ok(self.clientinfo(py).borrow().sampling_key())
+    }
+
+    /// Attach arbitrary key/value fields, serialized under an "extra"
+    /// object in to_json. Keys colliding with built-in fields raise.
+    def add_fields(&self, fields: Serde<std::collections::BTreeMap<String, String>>) -> PyResult<PyNone> {
+        self.clientinfo(py).borrow_mut().with_extra_fields(fields.0).map_pyerr(py)?;
+        Ok(PyNone)
+    }
 });
 
 py_class!(pub class ClientRequestInfo |py| {
@@ -70,6 +88,11 @@ py_class!(pub class ClientRequestInfo |py| {
         let client_request_info = client_info::ClientRequestInfo::new(entry_point);
         ClientRequestInfo::create_instance(py, RefCell::new(client_request_info))
     }
+
+    /// Milliseconds elapsed since this request info was created.
+    def elapsed_ms(&self) -> PyResult<u64> {
+        Ok(self.inner(py).borrow().elapsed().as_millis() as u64)
+    }
 });
 
 pub fn get_client_request_info(_py: Python) -> PyResult<Serde<client_info::ClientRequestInfo>> {
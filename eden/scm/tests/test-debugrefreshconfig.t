@@ -35,6 +35,20 @@ Create a repo
 
   $ newclientrepo client
 
+Verify --dry-run prints the config without writing anything
+
+  $ hg debugrefreshconfig --dry-run
+  # version=* (glob)
+  # reponame=* (glob)
+  # canary=None
+  # username=
+  # Generated by `hg debugrefreshconfig` - DO NOT MODIFY
+  [section]
+  key=value
+  
+  $ test -f .hg/hgrc.dynamic
+  [1]
+
 Verify it can be manually generated
 
   $ hg debugrefreshconfig
@@ -50,6 +64,43 @@ Verify it can be manually generated
   $ hg config section.key
   value
 
+Verify --diff shows what would change without writing anything
+
+  $ cat > $TESTTMP/test_hgrc <<EOF
+  > [section]
+  > key=changedvalue
+  > EOF
+  $ hg debugrefreshconfig --diff
+  @@ * @@ (glob)
+   # username=
+   # Generated by `hg debugrefreshconfig` - DO NOT MODIFY
+   [section]
+  -key=value
+  +key=changedvalue
+   
+  $ hg config section.key
+  value
+  $ cat > $TESTTMP/test_hgrc <<EOF
+  > [section]
+  > key=value
+  > EOF
+
+Verify --validate refuses to install a config that does not parse
+
+  $ cat > $TESTTMP/test_hgrc <<EOF
+  > [section
+  > key=value
+  > EOF
+  $ hg debugrefreshconfig --validate
+  invalid generated config: * (glob)
+  [1]
+  $ hg config section.key
+  value
+  $ cat > $TESTTMP/test_hgrc <<EOF
+  > [section]
+  > key=value
+  > EOF
+
 Verify it can be automatically synchronously generated
 
   $ rm .hg/hgrc.dynamic